@@ -0,0 +1,240 @@
+//! `#[pwasm_test]` attribute, see `pwasm_test::pwasm_test`
+#![recursion_limit = "128"]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{token, Ident, ItemFn, Lit};
+
+/// Argument of the attribute
+enum Arg {
+	/// `sender = "0x.."`
+	Value(Ident, Lit),
+	/// `balance("0x.." = 1000, ..)`
+	Entries(Ident, Vec<(Lit, Lit)>),
+	/// `print_trace`
+	Flag(Ident),
+}
+
+struct Entry {
+	key: Lit,
+	value: Lit,
+}
+
+struct Args(Vec<Arg>);
+
+impl Parse for Entry {
+	fn parse(input: ParseStream) -> syn::Result<Entry> {
+		let key = input.parse()?;
+		input.parse::<Token![=]>()?;
+		Ok(Entry {
+			key: key,
+			value: input.parse()?,
+		})
+	}
+}
+
+impl Parse for Arg {
+	fn parse(input: ParseStream) -> syn::Result<Arg> {
+		let name: Ident = input.parse()?;
+		if input.peek(Token![=]) {
+			input.parse::<Token![=]>()?;
+			Ok(Arg::Value(name, input.parse()?))
+		} else if input.peek(token::Paren) {
+			let content;
+			parenthesized!(content in input);
+			let entries: Punctuated<Entry, Token![,]> = content.parse_terminated(Entry::parse)?;
+			Ok(Arg::Entries(name, entries.into_iter().map(|entry| (entry.key, entry.value)).collect()))
+		} else {
+			Ok(Arg::Flag(name))
+		}
+	}
+}
+
+impl Parse for Args {
+	fn parse(input: ParseStream) -> syn::Result<Args> {
+		let args: Punctuated<Arg, Token![,]> = input.parse_terminated(Arg::parse)?;
+		Ok(Args(args.into_iter().collect()))
+	}
+}
+
+/// Turns a function into a test running in a freshly mocked world
+///
+/// Settings expand to the matching `ExternalBuilder` calls passed to `ext_reset`:
+///
+/// * `sender`, `origin`, `address`, `coinbase`: `"0x"`-prefixed address
+/// * `value`, `difficulty`, `gas_limit`: integer or a string with a decimal or hex number
+/// * `blocknumber`, `timestamp`, `block_time`: integer
+/// * `balance(address = amount, ..)`, `nonce(address = nonce, ..)`
+/// * `storage(key = value, ..)`: storage of the contract at `address`
+///
+/// Flags:
+///
+/// * `no_unexpected_calls`: fails the test if the contract called an address without an endpoint
+/// * `print_trace`: prints traces of all transactions if the test fails
+/// * `dump_storage`: prints decoded storage of contracts with a layout set by `ExternalBuilder::layout` if the test fails
+///
+/// ```ignore
+/// #[pwasm_test(sender = "0x16a0772b17ae004e6645e0e95bf50ad69498a34e", value = 100,
+/// 	balance("0x16a0772b17ae004e6645e0e95bf50ad69498a34e" = 1000), storage("0x01" = 7), print_trace)]
+/// fn transfer() {
+/// 	assert_eq!(pwasm_ethereum::value(), 100.into());
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn pwasm_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+	let args = parse_macro_input!(attr as Args);
+	let test = parse_macro_input!(item as ItemFn);
+	match expand(args, test) {
+		Ok(tokens) => tokens.into(),
+		Err(err) => err.to_compile_error().into(),
+	}
+}
+
+fn expand(args: Args, test: ItemFn) -> syn::Result<TokenStream2> {
+	if !test.decl.inputs.is_empty() {
+		return Err(syn::Error::new(test.ident.span(), "#[pwasm_test] functions can't take arguments"));
+	}
+	let mut setters = Vec::new();
+	let mut print_trace = false;
+	let mut dump_storage = false;
+	let mut no_unexpected_calls = false;
+	for arg in args.0 {
+		match arg {
+			Arg::Value(name, value) => setters.push(setter(&name, &value)?),
+			Arg::Entries(name, entries) => {
+				for (key, value) in entries {
+					setters.push(entry_setter(&name, &key, &value)?);
+				}
+			},
+			Arg::Flag(name) => match name.to_string().as_str() {
+				"print_trace" => print_trace = true,
+				"dump_storage" => dump_storage = true,
+				"no_unexpected_calls" => no_unexpected_calls = true,
+				_ => return Err(syn::Error::new(name.span(), "Unknown flag, expected `print_trace`, `dump_storage` or `no_unexpected_calls`")),
+			},
+		}
+	}
+
+	let attrs = &test.attrs;
+	let vis = &test.vis;
+	let ident = &test.ident;
+	let output = &test.decl.output;
+	let block = &test.block;
+	let printer = if print_trace {
+		quote!(let _trace_printer = ::pwasm_test::TracePrinter::new();)
+	} else {
+		quote!()
+	};
+	let storage_printer = if dump_storage {
+		quote!(let _storage_printer = ::pwasm_test::StoragePrinter::new();)
+	} else {
+		quote!()
+	};
+	let check = if no_unexpected_calls {
+		quote!(::pwasm_test::ext_assert_no_unexpected_calls();)
+	} else {
+		quote!()
+	};
+	Ok(quote! {
+		#[test]
+		#(#attrs)*
+		#vis fn #ident() #output {
+			#printer
+			#storage_printer
+			::pwasm_test::ext_reset(|e| e #(#setters)*);
+			let result = (|| #block)();
+			#check
+			result
+		}
+	})
+}
+
+fn setter(name: &Ident, value: &Lit) -> syn::Result<TokenStream2> {
+	match name.to_string().as_str() {
+		"sender" | "origin" | "address" | "coinbase" => {
+			let value = address(value)?;
+			Ok(quote!(.#name(#value)))
+		},
+		"value" | "difficulty" | "gas_limit" => {
+			let value = u256(value)?;
+			Ok(quote!(.#name(#value)))
+		},
+		"blocknumber" | "timestamp" | "block_time" => {
+			let value = u64_value(value)?;
+			Ok(quote!(.#name(#value)))
+		},
+		_ => Err(syn::Error::new(name.span(), "Unknown setting, expected one of `sender`, `origin`, `address`, `coinbase`, \
+			`value`, `difficulty`, `gas_limit`, `blocknumber`, `timestamp`, `block_time`")),
+	}
+}
+
+fn entry_setter(name: &Ident, key: &Lit, value: &Lit) -> syn::Result<TokenStream2> {
+	match name.to_string().as_str() {
+		"balance" => {
+			let (key, value) = (address(key)?, u256(value)?);
+			Ok(quote!(.balance_of(#key, #value)))
+		},
+		"nonce" => {
+			let (key, value) = (address(key)?, u256(value)?);
+			Ok(quote!(.nonce(#key, #value)))
+		},
+		"storage" => {
+			let (key, value) = (number(key)?, number(value)?);
+			Ok(quote!(.storage(::pwasm_test::macro_support::h256(#key), ::pwasm_test::macro_support::word(#value))))
+		},
+		_ => Err(syn::Error::new(name.span(), "Unknown setting, expected one of `balance`, `nonce`, `storage`")),
+	}
+}
+
+/// `"0x.."` with 20 bytes
+fn address(lit: &Lit) -> syn::Result<TokenStream2> {
+	match *lit {
+		Lit::Str(ref s) if is_hex(&s.value(), 40) && s.value().len() == 42 => Ok(quote!(::pwasm_test::macro_support::address(#s))),
+		_ => Err(syn::Error::new(lit.span(), "Expected an address, e.g. \"0x16a0772b17ae004e6645e0e95bf50ad69498a34e\"")),
+	}
+}
+
+fn u256(lit: &Lit) -> syn::Result<TokenStream2> {
+	let value = number(lit)?;
+	Ok(quote!(::pwasm_test::macro_support::u256(#value)))
+}
+
+/// Integer or a string with a hex or decimal number of at most 256 bits, as a string
+fn number(lit: &Lit) -> syn::Result<String> {
+	match *lit {
+		Lit::Int(ref int) => Ok(int.value().to_string()),
+		Lit::Str(ref s) => {
+			let value = s.value();
+			if is_hex(&value, 64) || (!value.is_empty() && value.len() <= 78 && value.chars().all(|c| c.is_digit(10))) {
+				Ok(value)
+			} else {
+				Err(syn::Error::new(lit.span(), "Expected a decimal or a 0x-prefixed hex number"))
+			}
+		},
+		_ => Err(syn::Error::new(lit.span(), "Expected an integer or a string with a number")),
+	}
+}
+
+fn u64_value(lit: &Lit) -> syn::Result<u64> {
+	match *lit {
+		Lit::Int(ref int) => Ok(int.value()),
+		_ => Err(syn::Error::new(lit.span(), "Expected an integer")),
+	}
+}
+
+/// `0x`-prefixed hex with at most `max_digits` digits
+fn is_hex(s: &str, max_digits: usize) -> bool {
+	(s.starts_with("0x") || s.starts_with("0X"))
+		&& s.len() > 2
+		&& s.len() - 2 <= max_digits
+		&& s[2..].chars().all(|c| c.is_digit(16))
+}
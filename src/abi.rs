@@ -0,0 +1,177 @@
+//! ABI encoding and signature-driven decoding of call inputs
+use std::fmt;
+
+use pwasm_std::types::{H256, U256, Address};
+use pwasm_abi::eth::{AbiType, Sink, Stream};
+use tiny_keccak::keccak256;
+
+use accounts;
+use hex;
+use prelude::*;
+
+/// Arguments which can be ABI-encoded as a call input
+pub trait AbiArgs {
+	/// Encodes arguments without a selector
+	fn encode(self) -> Vec<u8>;
+}
+
+impl AbiArgs for () {
+	fn encode(self) -> Vec<u8> {
+		Vec::new()
+	}
+}
+
+macro_rules! impl_abi_args {
+	($capacity: expr, $($t: ident $v: ident),+) => {
+		impl<$($t: AbiType),+> AbiArgs for ($($t,)+) {
+			fn encode(self) -> Vec<u8> {
+				let ($($v,)+) = self;
+				let mut sink = Sink::new($capacity);
+				$(sink.push($v);)+
+				sink.finalize_panicking()
+			}
+		}
+	}
+}
+
+impl_abi_args!(1, A a);
+impl_abi_args!(2, A a, B b);
+impl_abi_args!(3, A a, B b, C c);
+impl_abi_args!(4, A a, B b, C c, D d);
+impl_abi_args!(5, A a, B b, C c, D d, E e);
+impl_abi_args!(6, A a, B b, C c, D d, E e, F f);
+
+/// Method signature like `transfer(address,uint256)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+	/// Method name
+	pub name: String,
+	/// Parameter types
+	pub params: Vec<String>,
+	/// First 4 bytes of keccak of the signature
+	pub selector: [u8; 4],
+}
+
+impl Signature {
+	/// Parses a signature, whitespace is ignored
+	pub fn parse(signature: &str) -> Result<Signature, String> {
+		let signature: String = signature.chars().filter(|c| !c.is_whitespace()).collect();
+		let open = match signature.find('(') {
+			Some(open) if open > 0 && signature.ends_with(')') => open,
+			_ => return Err(format!("{} is not a method signature, e.g. transfer(address,uint256)", signature)),
+		};
+		let params = &signature[open + 1..signature.len() - 1];
+		if params.contains('(') || params.contains(')') {
+			return Err(format!("tuple parameters of {} are not supported", signature));
+		}
+		let mut selector = [0u8; 4];
+		selector.copy_from_slice(&keccak256(signature.as_bytes())[..4]);
+		Ok(Signature {
+			name: signature[..open].to_owned(),
+			params: if params.is_empty() { Vec::new() } else { params.split(',').map(ToOwned::to_owned).collect() },
+			selector: selector,
+		})
+	}
+
+	/// Decodes arguments, `data` is the call input without a selector
+	pub fn decode(&self, data: &[u8]) -> Result<Vec<String>, String> {
+		decode_params(&self.params, data)
+	}
+
+	/// Keccak of the signature, the first topic of logs of an event with this signature
+	pub fn topic(&self) -> H256 {
+		H256::from_slice(&keccak256(self.to_string().as_bytes()))
+	}
+
+	/// Formats `input` as `name(arg, ..)` if it starts with the selector, as hex otherwise
+	pub fn format_input(&self, input: &[u8]) -> String {
+		if input.len() < 4 || input[..4] != self.selector {
+			return hex::to_hex(input);
+		}
+		match self.decode(&input[4..]) {
+			Ok(args) => format!("{}({})", self.name, args.join(", ")),
+			Err(_) => format!("{}(undecodable {})", self.name, hex::to_hex(&input[4..])),
+		}
+	}
+}
+
+/// Canonical form of the signature, e.g. `transfer(address,uint256)`
+impl fmt::Display for Signature {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}({})", self.name, self.params.join(","))
+	}
+}
+
+/// Methods and events of a contract, see `ExternalBuilder::abi`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Abi {
+	pub methods: Vec<Signature>,
+	pub events: Vec<Signature>,
+}
+
+impl Abi {
+	/// Parses signatures of methods and events, events are prefixed with `event `
+	///
+	/// `["transfer(address,uint256)", "event Transfer(address,address,uint256)"]`
+	pub fn parse(entries: &[&str]) -> Result<Abi, String> {
+		let mut abi = Abi::default();
+		for entry in entries.iter().map(|entry| entry.trim()) {
+			if entry.starts_with("event ") {
+				abi.events.push(Signature::parse(&entry["event ".len()..])?);
+			} else if entry.starts_with("function ") {
+				abi.methods.push(Signature::parse(&entry["function ".len()..])?);
+			} else {
+				abi.methods.push(Signature::parse(entry)?);
+			}
+		}
+		Ok(abi)
+	}
+
+	/// Method called by `input`, `None` if its selector isn't in the ABI
+	pub fn method(&self, input: &[u8]) -> Option<&Signature> {
+		if input.len() < 4 {
+			return None;
+		}
+		self.methods.iter().find(|method| method.selector[..] == input[..4])
+	}
+
+	/// Event with `topic` as its first topic
+	pub fn event(&self, topic: &H256) -> Option<&Signature> {
+		self.events.iter().find(|event| event.topic() == *topic)
+	}
+}
+
+/// Selector of a method with `signature`, e.g. `transfer(address,uint256)`
+pub fn selector(signature: &str) -> [u8; 4] {
+	match Signature::parse(signature) {
+		Ok(signature) => signature.selector,
+		Err(err) => panic!("{}", err),
+	}
+}
+
+/// Decodes ABI-encoded values of `params` types
+pub fn decode_params(params: &[String], data: &[u8]) -> Result<Vec<String>, String> {
+	let mut stream = Stream::new(data);
+	params.iter().map(|param| decode_param(&mut stream, param)).collect()
+}
+
+fn pop<T: AbiType>(stream: &mut Stream, param: &str) -> Result<T, String> {
+	stream.pop::<T>().map_err(|_| format!("can't decode {}", param))
+}
+
+fn decode_param(stream: &mut Stream, param: &str) -> Result<String, String> {
+	Ok(match param {
+		"address" => format_address(&pop::<Address>(stream, param)?),
+		"bool" => pop::<bool>(stream, param)?.to_string(),
+		"bytes" => hex::to_hex(&pop::<Vec<u8>>(stream, param)?),
+		"string" => format!("{:?}", pop::<String>(stream, param)?),
+		param if param.starts_with("uint") && !param.ends_with(']') => pop::<U256>(stream, param)?.to_string(),
+		param if (param.starts_with("int") || param.starts_with("bytes")) && !param.ends_with(']') => hex::to_hex(&pop::<H256>(stream, param)?),
+		param => return Err(format!("{} parameters are not supported", param)),
+	})
+}
+
+/// Formats an address in decoded call inputs, showing its name if it has one
+pub fn format_address(address: &Address) -> String {
+	accounts::format_address(address)
+}
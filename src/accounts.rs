@@ -0,0 +1,89 @@
+//! Address book of named test accounts
+//!
+//! Addresses are derived from names, so `alice()` is the same in every test.
+//! Names of these accounts and of addresses labeled with `ExternalBuilder::label`
+//! are shown in traces and assertion messages instead of hex.
+//!
+//! ```
+//! # extern crate pwasm_test;
+//! # extern crate pwasm_ethereum;
+//! # use pwasm_test::ext_reset;
+//! # use pwasm_test::accounts::{alice, bob};
+//! # fn main () {
+//! #
+//!	ext_reset(|e| e.funded_accounts(2, 1000.into()).sender(alice()));
+//!	assert_eq!(pwasm_ethereum::balance(&bob()), 1000.into());
+//! # }
+//! ```
+use pwasm_std::types::Address;
+use tiny_keccak::keccak256;
+
+use external::ExternalInstance;
+use externs::installed_external;
+use hex;
+use prelude::*;
+
+/// Names of the first accounts, further ones are called `account8`, `account9` and so on
+const NAMES: &[&str] = &["alice", "bob", "carol", "dave", "eve", "frank", "grace", "heidi"];
+
+/// Address derived from `name`: the last 20 bytes of its keccak
+pub fn named(name: &str) -> Address {
+	Address::from_slice(&keccak256(name.as_bytes())[12..])
+}
+
+/// Name of the `index`th account of the book
+pub fn name(index: usize) -> String {
+	match NAMES.get(index) {
+		Some(name) => (*name).to_owned(),
+		None => format!("account{}", index),
+	}
+}
+
+/// `index`th account of the book, `account(0)` is `alice()`
+pub fn account(index: usize) -> Address {
+	named(&name(index))
+}
+
+/// Named accounts of the book, `alice()` to `heidi()`
+pub(crate) fn book() -> Vec<Address> {
+	NAMES.iter().map(|name| named(name)).collect()
+}
+
+pub fn alice() -> Address { named("alice") }
+pub fn bob() -> Address { named("bob") }
+pub fn carol() -> Address { named("carol") }
+pub fn dave() -> Address { named("dave") }
+pub fn eve() -> Address { named("eve") }
+pub fn frank() -> Address { named("frank") }
+pub fn grace() -> Address { named("grace") }
+pub fn heidi() -> Address { named("heidi") }
+
+/// Runs `f` with the installed `ExternalInstance`, `None` if another external is installed
+pub(crate) fn with_instance<T, F: FnOnce(&ExternalInstance) -> Option<T>>(f: F) -> Option<T> {
+	let installed = installed_external().ok()?;
+	let instance = installed.as_any().downcast_ref::<ExternalInstance>()?;
+	f(instance)
+}
+
+/// Label of `address` in the installed world or the name of a book account
+pub fn name_of(address: &Address) -> Option<String> {
+	with_instance(|instance| instance.labels.get(address).cloned())
+		.or_else(|| NAMES.iter().find(|name| named(name) == *address).map(|name| (*name).to_owned()))
+}
+
+/// Name of `address` if it has one, hex otherwise
+pub fn format_address(address: &Address) -> String {
+	name_of(address).unwrap_or_else(|| hex::to_hex(address))
+}
+
+/// Decoded `method(args)` if the selector of `input` was registered with `ExternalBuilder::signature`
+pub(crate) fn format_method(input: &[u8]) -> Option<String> {
+	if input.len() < 4 {
+		return None;
+	}
+	with_instance(|instance| {
+		instance.signatures.iter()
+			.find(|signature| signature.selector[..] == input[..4])
+			.map(|signature| signature.format_input(input))
+	})
+}
@@ -0,0 +1,252 @@
+//! Assertions on logs, calls and storage with readable failure messages
+use std::cmp;
+
+use pwasm_std::types::{H256, Address};
+use tiny_keccak::keccak256;
+
+use abi::{self, Signature};
+use external::{Call, ExternalInstance, LogEntry};
+use externs::get_external;
+use hex;
+use prelude::*;
+
+/// Event declaration like `Transfer(address indexed from, address indexed to, uint256 value)`
+struct Event {
+	name: String,
+	params: Vec<String>,
+	indexed: Vec<bool>,
+	topic: H256,
+}
+
+impl Event {
+	fn parse(declaration: &str) -> Event {
+		let (name, params) = match (declaration.find('('), declaration.rfind(')')) {
+			(Some(open), Some(close)) if open < close => (declaration[..open].trim(), &declaration[open + 1..close]),
+			_ => panic!("{} is not an event declaration, e.g. Transfer(address indexed,address indexed,uint256)", declaration),
+		};
+		let mut event = Event {
+			name: name.to_owned(),
+			params: Vec::new(),
+			indexed: Vec::new(),
+			topic: H256::zero(),
+		};
+		for param in params.split(',').map(str::trim).filter(|param| !param.is_empty()) {
+			let mut words = param.split_whitespace();
+			event.params.push(words.next().unwrap_or_default().to_owned());
+			event.indexed.push(words.next() == Some("indexed"));
+		}
+		let signature = format!("{}({})", event.name, event.params.join(","));
+		event.topic = H256::from_slice(&keccak256(signature.as_bytes()));
+		event
+	}
+
+	/// Formats `entry` as `Name(arg, ..)` if its first topic is the event topic
+	fn format(&self, entry: &LogEntry) -> String {
+		if entry.topics.first() != Some(&self.topic) {
+			return format!("not {}", self.name);
+		}
+		let mut topics = entry.topics[1..].iter();
+		let data_params: Vec<String> = self.params.iter().zip(self.indexed.iter())
+			.filter(|&(_, indexed)| !indexed)
+			.map(|(param, _)| param.clone())
+			.collect();
+		let mut data = match abi::decode_params(&data_params, &entry.data) {
+			Ok(values) => values.into_iter(),
+			Err(err) => return format!("{}(undecodable data: {})", self.name, err),
+		};
+		let args: Vec<String> = self.params.iter().zip(self.indexed.iter()).map(|(param, indexed)| {
+			if *indexed {
+				match topics.next() {
+					Some(topic) => abi::decode_params(&[param.clone()], topic)
+						.ok()
+						.and_then(|mut values| values.pop())
+						.unwrap_or_else(|| hex::to_hex(topic)),
+					None => "<missing topic>".to_owned(),
+				}
+			} else {
+				data.next().unwrap_or_default()
+			}
+		}).collect();
+		format!("{}({})", self.name, args.join(", "))
+	}
+}
+
+/// Index of the first differing byte, `None` if `expected` and `actual` are equal
+fn first_mismatch(expected: &[u8], actual: &[u8]) -> Option<usize> {
+	if expected == actual {
+		return None;
+	}
+	Some(expected.iter().zip(actual.iter()).position(|(e, a)| e != a).unwrap_or(cmp::min(expected.len(), actual.len())))
+}
+
+/// `index`th 32-byte word of `bytes` as hex, `-` past the end
+fn word(bytes: &[u8], index: usize) -> String {
+	let start = index * 32;
+	if start >= bytes.len() {
+		"-".to_owned()
+	} else {
+		hex::to_hex(&bytes[start..cmp::min(start + 32, bytes.len())])
+	}
+}
+
+/// Side-by-side diff of `actual` and `expected` split into 32-byte words, `None` if they are equal
+///
+/// Arguments are in the order of the `assert_*_eq` functions, the expected column is shown first.
+pub fn diff_bytes(actual: &[u8], expected: &[u8]) -> Option<String> {
+	let first = first_mismatch(expected, actual)?;
+	let mut diff = format!("differ from byte {} (word {}), expected {} bytes, actual {} bytes\n",
+		first, first / 32, expected.len(), actual.len());
+	diff.push_str(&format!("    {:<5} {:<66} | {}\n", "word", "expected", "actual"));
+	let words = (cmp::max(expected.len(), actual.len()) + 31) / 32;
+	for index in 0..words {
+		let (expected, actual) = (word(expected, index), word(actual, index));
+		let marker = if expected == actual { ' ' } else { '>' };
+		diff.push_str(&format!("  {} {:<5} {:<66} | {}\n", marker, index, expected, actual));
+	}
+	Some(diff)
+}
+
+/// Differences of log entries, the block number isn't compared
+fn log_diff(actual: &LogEntry, expected: &LogEntry, event: Option<&Event>) -> Option<String> {
+	let mut diff = String::new();
+	if actual.topics.len() != expected.topics.len() {
+		diff.push_str(&format!("  number of topics: expected {}, actual {}\n", expected.topics.len(), actual.topics.len()));
+	}
+	for (index, (expected, actual)) in expected.topics.iter().zip(actual.topics.iter()).enumerate() {
+		if expected != actual {
+			diff.push_str(&format!("  topic {} differs:\n    expected {}\n    actual   {}\n", index, hex::to_hex(expected), hex::to_hex(actual)));
+		}
+	}
+	if let Some(data) = diff_bytes(&actual.data, &expected.data) {
+		diff.push_str(&format!("  data {}", data));
+	}
+	if diff.is_empty() {
+		return None;
+	}
+	if let Some(event) = event {
+		diff.push_str(&format!("  decoded:\n    expected {}\n    actual   {}\n", event.format(expected), event.format(actual)));
+	}
+	Some(diff)
+}
+
+/// Panics with a diff if log entries differ in topics or data
+///
+/// # Example
+/// ```should_panic
+/// # extern crate pwasm_test;
+/// # extern crate pwasm_std;
+/// # use pwasm_std::types::H256;
+/// # use pwasm_test::{assert_log_eq, LogEntry};
+/// # fn main () {
+/// #
+///	let expected = LogEntry::new(&[H256::zero()], &[1, 2, 3]);
+///	let actual = LogEntry::new(&[H256::zero()], &[1, 2, 4]);
+///	// Panics with:
+///	//   data differ from byte 2 (word 0), expected 3 bytes, actual 3 bytes
+///	//     word  expected                                                           | actual
+///	//   > 0     0x010203                                                           | 0x010204
+///	assert_log_eq(&actual, &expected);
+/// # }
+/// ```
+pub fn assert_log_eq(actual: &LogEntry, expected: &LogEntry) {
+	if let Some(diff) = log_diff(actual, expected, None) {
+		panic!("Log entries differ:\n{}", diff);
+	}
+}
+
+/// Panics with a diff and decoded arguments if log entries of `event` differ
+///
+/// `event` is a declaration with indexed parameters marked, e.g. `Transfer(address indexed, address indexed, uint256)`.
+pub fn assert_event_eq(actual: &LogEntry, expected: &LogEntry, event: &str) {
+	let event = Event::parse(event);
+	if let Some(diff) = log_diff(actual, expected, Some(&event)) {
+		panic!("{} log entries differ:\n{}", event.name, diff);
+	}
+}
+
+fn call_diff(actual: &Call, expected: &Call, signature: Option<&Signature>) -> Option<String> {
+	let mut diff = String::new();
+	if actual.address != expected.address {
+		diff.push_str(&format!("  address:\n    expected {}\n    actual   {}\n", abi::format_address(&expected.address), abi::format_address(&actual.address)));
+	}
+	if actual.value != expected.value {
+		diff.push_str(&format!("  value: expected {}, actual {}\n", expected.value, actual.value));
+	}
+	if actual.gas != expected.gas {
+		diff.push_str(&format!("  gas: expected {}, actual {}\n", expected.gas, actual.gas));
+	}
+	if let Some(input) = diff_bytes(&actual.input, &expected.input) {
+		diff.push_str(&format!("  input {}", input));
+		if let Some(signature) = signature {
+			diff.push_str(&format!("  decoded:\n    expected {}\n    actual   {}\n",
+				signature.format_input(&expected.input), signature.format_input(&actual.input)));
+		}
+	}
+	if diff.is_empty() { None } else { Some(diff) }
+}
+
+/// Panics with a diff if calls differ in address, value, gas or input
+pub fn assert_call_eq(actual: &Call, expected: &Call) {
+	if let Some(diff) = call_diff(actual, expected, None) {
+		panic!("Calls differ:\n{}", diff);
+	}
+}
+
+/// Panics with a diff and decoded inputs if calls of a method with `signature` differ
+///
+/// # Example
+/// ```should_panic
+/// # extern crate pwasm_test;
+/// # use pwasm_test::{assert_method_call_eq, selector, Call};
+/// # fn main () {
+/// #
+///	let mut input = selector("approve(uint256)").to_vec();
+///	input.extend_from_slice(&[0; 32]);
+///	let expected = Call { input: input.clone().into(), ..Call::default() };
+///	input[35] = 1;
+///	let actual = Call { input: input.into(), ..Call::default() };
+///	// Panics with the input diff followed by:
+///	//   decoded:
+///	//     expected approve(0)
+///	//     actual   approve(1)
+///	assert_method_call_eq(&actual, &expected, "approve(uint256)");
+/// # }
+/// ```
+pub fn assert_method_call_eq(actual: &Call, expected: &Call, signature: &str) {
+	let signature = match Signature::parse(signature) {
+		Ok(signature) => signature,
+		Err(err) => panic!("{}", err),
+	};
+	if let Some(diff) = call_diff(actual, expected, Some(&signature)) {
+		panic!("Calls of {} differ:\n{}", signature.name, diff);
+	}
+}
+
+/// Panics with a diff if storage words differ
+pub fn assert_word_eq(actual: &[u8; 32], expected: &[u8; 32]) {
+	if let Some(diff) = diff_bytes(actual, expected) {
+		panic!("Storage words {}", diff);
+	}
+}
+
+/// Panics with a diff if storage `key` of the contract at `address` doesn't hold `expected`
+///
+/// # Example
+/// ```
+/// # extern crate pwasm_test;
+/// # extern crate pwasm_std;
+/// # use pwasm_std::types::H256;
+/// # use pwasm_test::{ext_reset, ext_assert_storage};
+/// # fn main () {
+/// #
+///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///	ext_reset(|e| e.storage_at(contract, H256::zero(), [7; 32]));
+///	ext_assert_storage(&contract, &H256::zero(), &[7; 32]);
+/// # }
+/// ```
+pub fn ext_assert_storage(address: &Address, key: &H256, expected: &[u8; 32]) {
+	let actual = get_external::<ExternalInstance>().storage_at(address, key);
+	if let Some(diff) = diff_bytes(&actual, expected) {
+		panic!("Storage {} of {} {}", hex::to_hex(key), abi::format_address(address), diff);
+	}
+}
@@ -1,15 +1,26 @@
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::BTreeMap;
 use std::cell::RefCell;
 
 use pwasm_std::types::{H256, U256, Address};
 use external::{ExternalInstance, Endpoint};
+use trace::Tracer;
+use fault::{Fault, Faults};
+use abi::{Abi, Signature};
+use accounts;
+use slots::Word;
+use layout::Layout;
+use prelude::*;
 
 /// A builder for quick creation of External impls for testing.
 pub struct ExternalBuilder {
-	storage: HashMap<H256, [u8; 32]>,
-	balances: HashMap<Address, U256>,
-	endpoints: HashMap<Address, Rc<RefCell<Endpoint>>>,
+	/// Words set with `storage` in this builder pass, written to the contract at `address` when built
+	storage: BTreeMap<H256, [u8; 32]>,
+	account_storage: BTreeMap<Address, BTreeMap<H256, [u8; 32]>>,
+	balances: BTreeMap<Address, U256>,
+	nonces: BTreeMap<Address, U256>,
+	code: BTreeMap<Address, Vec<u8>>,
+	code_hashes: BTreeMap<Address, H256>,
+	endpoints: BTreeMap<Address, Endpoint>,
 	value: U256,
 	sender: Address,
 	address: Address,
@@ -19,17 +30,31 @@ pub struct ExternalBuilder {
 	gas_limit: U256,
 	blocknumber: u64,
 	timestamp: u64,
+	block_time: u64,
+	blockhashes: BTreeMap<u64, H256>,
+	faults: Faults,
+	labels: BTreeMap<Address, String>,
+	signatures: Vec<Signature>,
+	layouts: BTreeMap<Address, Layout>,
+	abis: BTreeMap<Address, Abi>,
 }
 
+/// Default number of seconds between mined blocks
+const DEFAULT_BLOCK_TIME: u64 = 15;
+
 impl ExternalBuilder {
 	/// Begin build process
 	pub fn new() -> Self {
 		ExternalBuilder {
-			storage: HashMap::new(),
-			endpoints: HashMap::new(),
+			storage: BTreeMap::new(),
+			account_storage: BTreeMap::new(),
+			endpoints: BTreeMap::new(),
 			sender: Address::default(),
 			address: Address::default(),
-			balances: HashMap::new(),
+			balances: BTreeMap::new(),
+			nonces: BTreeMap::new(),
+			code: BTreeMap::new(),
+			code_hashes: BTreeMap::new(),
 			value: U256::zero(),
 			origin: Address::default(),
 			coinbase: Address::default(),
@@ -37,6 +62,13 @@ impl ExternalBuilder {
 			gas_limit: U256::zero(),
 			blocknumber: 0u64,
 			timestamp: 0u64,
+			block_time: DEFAULT_BLOCK_TIME,
+			blockhashes: BTreeMap::new(),
+			faults: Faults::default(),
+			labels: BTreeMap::new(),
+			signatures: Vec::new(),
+			layouts: BTreeMap::new(),
+			abis: BTreeMap::new(),
 		}
 	}
 
@@ -65,11 +97,11 @@ impl ExternalBuilder {
 	/// ```
 	///
 	pub fn endpoint(mut self, address: Address, endpoint: Endpoint) -> Self {
-		self.endpoints.insert(address, Rc::new(RefCell::new(endpoint)));
+		self.endpoints.insert(address, endpoint);
 		self
 	}
 
-	/// Sets storage value for some `key`
+	/// Sets storage value for some `key` of the contract at `pwasm_ethereum::address()`
 	///
 	/// # Example
 	/// ```
@@ -89,6 +121,45 @@ impl ExternalBuilder {
 		self
 	}
 
+	/// Sets storage value for some `key` of the contract at `pwasm_ethereum::address()` encoded as a word
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::{H256, U256};
+	/// # use pwasm_test::{ext_reset, ext_get};
+	/// # fn main () {
+	/// #
+	///	ext_reset(|e| e.storage_value(H256::zero(), U256::from(10000)));
+	///	assert_eq!(ext_get().storage_u256(&H256::zero()), 10000.into());
+	/// # }
+	/// ```
+	pub fn storage_value<T: Word>(self, key: H256, value: T) -> Self {
+		self.storage(key, value.to_word())
+	}
+
+	/// Sets storage value for some `key` of the contract at `address`
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::H256;
+	/// # use pwasm_test::ext_reset;
+	/// # fn main () {
+	/// #
+	///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e.storage_at(contract, H256::zero(), [250; 32]).address(contract));
+	///	assert_eq!(pwasm_ethereum::read(&H256::zero()), [250; 32]);
+	/// # }
+	/// ```
+	pub fn storage_at(mut self, address: Address, key: H256, value: [u8; 32]) -> Self {
+		self.account_storage.entry(address).or_insert_with(BTreeMap::new).insert(key, value);
+		self
+	}
+
 	/// Sets `pwasm_ethereum::balance()` for some address
 	///
 	/// # Example
@@ -109,6 +180,198 @@ impl ExternalBuilder {
 		self
 	}
 
+	/// Sets nonce of some address
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # use pwasm_test::{ext_reset, ext_get};
+	/// # fn main () {
+	/// #
+	///	let owner = "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap();
+	///	ext_reset(|e| e.nonce(owner, 5.into()));
+	///	assert_eq!(ext_get().nonce(&owner), 5.into());
+	/// # }
+	/// ```
+	pub fn nonce(mut self, address: Address, nonce: U256) -> Self {
+		self.nonces.insert(address, nonce);
+		self
+	}
+
+	/// Deploys `code` at some address
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # use pwasm_test::{ext_reset, ext_get};
+	/// # fn main () {
+	/// #
+	///	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e.code(token, vec![0, 0x61, 0x73, 0x6d]));
+	///	assert_eq!(ext_get().code_at(&token), vec![0, 0x61, 0x73, 0x6d]);
+	///	assert!(ext_get().is_contract(&token));
+	/// # }
+	/// ```
+	pub fn code(mut self, address: Address, code: Vec<u8>) -> Self {
+		self.code.insert(address, code);
+		self
+	}
+
+	/// Sets code hash of some address without providing the code itself
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::H256;
+	/// # use pwasm_test::{ext_reset, ext_get};
+	/// # fn main () {
+	/// #
+	///	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e.code_hash(token, H256::from_slice(&[7; 32])));
+	///	assert_eq!(ext_get().code_hash(&token), H256::from_slice(&[7; 32]));
+	///	assert!(ext_get().is_contract(&token));
+	/// # }
+	/// ```
+	pub fn code_hash(mut self, address: Address, hash: H256) -> Self {
+		self.code_hashes.insert(address, hash);
+		self
+	}
+
+	/// Funds the first `count` accounts of the address book (`alice`, `bob`, ..) with `amount` each
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # use pwasm_test::ext_reset;
+	/// # use pwasm_test::accounts::{alice, carol, dave};
+	/// # fn main () {
+	/// #
+	///	ext_reset(|e| e.funded_accounts(3, 1000.into()));
+	///	assert_eq!(pwasm_ethereum::balance(&alice()), 1000.into());
+	///	assert_eq!(pwasm_ethereum::balance(&carol()), 1000.into());
+	///	assert_eq!(pwasm_ethereum::balance(&dave()), 0.into());
+	/// # }
+	/// ```
+	pub fn funded_accounts(mut self, count: usize, amount: U256) -> Self {
+		for index in 0..count {
+			let address = accounts::account(index);
+			self.balances.insert(address, amount);
+			self.labels.insert(address, accounts::name(index));
+		}
+		self
+	}
+
+	/// Names some address in traces and assertion messages
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # use pwasm_test::ext_reset;
+	/// # use pwasm_test::accounts::format_address;
+	/// # fn main () {
+	/// #
+	///	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e.label(token, "token"));
+	///	assert_eq!(format_address(&token), "token");
+	/// # }
+	/// ```
+	pub fn label(mut self, address: Address, name: &str) -> Self {
+		self.labels.insert(address, name.to_owned());
+		self
+	}
+
+	/// Registers a method signature, e.g. `transfer(address,uint256)`,
+	/// so traces show inputs with its selector decoded
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::U256;
+	/// # use pwasm_test::{ext_reset, ext_transact, selector, Endpoint};
+	/// # use pwasm_test::accounts::{self, alice, bob};
+	/// # use pwasm_test::slots::Word;
+	/// # fn main () {
+	/// #
+	///	let token = accounts::named("token");
+	///	ext_reset(|e| e
+	///		.label(token, "token")
+	///		.endpoint(token, Endpoint::ok())
+	///		.signature("transfer(address,uint256)")
+	///	);
+	///	let mut input = selector("transfer(address,uint256)").to_vec();
+	///	input.extend_from_slice(&bob().to_word());
+	///	input.extend_from_slice(&U256::from(100).to_word());
+	///	let receipt = ext_transact(alice(), token, 0.into(), &input);
+	///	assert!(receipt.trace.to_string().contains("CALL alice -> token.transfer(bob, 100)"));
+	/// # }
+	/// ```
+	pub fn signature(mut self, signature: &str) -> Self {
+		match Signature::parse(signature) {
+			Ok(signature) => self.signatures.push(signature),
+			Err(err) => panic!("{}", err),
+		}
+		self
+	}
+
+	/// Declares methods and events of the contract at `address`, events are prefixed with `event `
+	///
+	/// Methods are decoded in traces like ones added with `signature`, the ABI coverage report
+	/// lists every declared method and event of the contract including ones never reached.
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # use pwasm_test::{ext_reset, ext_get};
+	/// # fn main () {
+	/// #
+	///	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e.abi(token, &["transfer(address,uint256)", "event Transfer(address,address,uint256)"]));
+	///	assert_eq!(ext_get().abis[&token].methods[0].name, "transfer");
+	/// # }
+	/// ```
+	pub fn abi(mut self, address: Address, entries: &[&str]) -> Self {
+		let abi = match Abi::parse(entries) {
+			Ok(abi) => abi,
+			Err(err) => panic!("{}", err),
+		};
+		for method in &abi.methods {
+			if !self.signatures.contains(method) {
+				self.signatures.push(method.clone());
+			}
+		}
+		self.abis.insert(address, abi);
+		self
+	}
+
+	/// Declares storage layout of the contract at `address` for `ext_dump_storage` and `ext_assert_storage_layout`
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::U256;
+	/// # use pwasm_test::{ext_reset, ext_dump_storage};
+	/// # use pwasm_test::accounts;
+	/// # use pwasm_test::layout::Layout;
+	/// # use pwasm_test::slots::{self, Word};
+	/// # fn main () {
+	/// #
+	///	let vault = accounts::named("vault");
+	///	ext_reset(|e| e
+	///		.layout(vault, Layout::new().value("owner", 0, "address").value("deposits", 1, "uint256"))
+	///		.storage_at(vault, slots::slot(1), U256::from(500).to_word())
+	///	);
+	///	assert_eq!(ext_dump_storage(&vault), "owner = 0x0000000000000000000000000000000000000000\ndeposits = 500");
+	/// # }
+	/// ```
+	pub fn layout(mut self, address: Address, layout: Layout) -> Self {
+		self.layouts.insert(address, layout);
+		self
+	}
+
 	/// Sets `pwasm_ethereum::sender()`
 	///
 	/// # Example
@@ -185,6 +448,45 @@ impl ExternalBuilder {
 		self
 	}
 
+	/// Sets number of seconds `ext_mine` moves `pwasm_ethereum::timestamp()` per block
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # use pwasm_test::{ext_reset, ext_mine};
+	/// # fn main () {
+	/// #
+	///	ext_reset(|e| e.timestamp(100).block_time(5));
+	///	ext_mine(1);
+	///	assert_eq!(pwasm_ethereum::timestamp(), 105);
+	/// # }
+	/// ```
+	pub fn block_time(mut self, block_time: u64) -> Self {
+		self.block_time = block_time;
+		self
+	}
+
+	/// Sets `pwasm_ethereum::block_hash()` for some block number
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::H256;
+	/// # use pwasm_test::ext_reset;
+	/// # fn main () {
+	/// #
+	///	ext_reset(|e| e.blockhash(5, H256::from_slice(&[1; 32])));
+	///	assert_eq!(pwasm_ethereum::block_hash(5).unwrap(), H256::from_slice(&[1; 32]));
+	/// # }
+	/// ```
+	pub fn blockhash(mut self, number: u64, hash: H256) -> Self {
+		self.blockhashes.insert(number, hash);
+		self
+	}
+
 	/// Sets `pwasm_ethereum::difficulty()`
 	///
 	/// # Example
@@ -276,14 +578,128 @@ impl ExternalBuilder {
 		self
 	}
 
+	/// Makes the `nth` (starting from 1) `pwasm_ethereum::call` to `address` fail
+	///
+	/// Injected faults which were hit are listed by `ext_get().faults.report()`.
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # use pwasm_test::{ext_reset, ext_get, Endpoint};
+	/// # fn main () {
+	/// #
+	///	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e.endpoint(token, Endpoint::ok()).fail_call(token, 2));
+	///	assert!(pwasm_ethereum::call(2000, &token, 0.into(), &[], &mut []).is_ok());
+	///	assert!(pwasm_ethereum::call(2000, &token, 0.into(), &[], &mut []).is_err());
+	///	ext_get().faults.report().assert_all_hit();
+	/// # }
+	/// ```
+	pub fn fail_call(mut self, address: Address, nth: usize) -> Self {
+		self.faults.push(Fault::CallFails { address: address, nth: nth });
+		self
+	}
+
+	/// Makes `pwasm_ethereum::create` fail
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # use pwasm_test::ext_reset;
+	/// # fn main () {
+	/// #
+	///	ext_reset(|e| e.fail_create());
+	///	assert!(pwasm_ethereum::create(0.into(), &[1]).is_err());
+	/// # }
+	/// ```
+	pub fn fail_create(mut self) -> Self {
+		self.faults.push(Fault::CreateFails);
+		self
+	}
+
+	/// Makes `pwasm_ethereum::block_hash` return an error
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::H256;
+	/// # use pwasm_test::ext_reset;
+	/// # fn main () {
+	/// #
+	///	ext_reset(|e| e.blockhash(5, H256::from_slice(&[1; 32])).fail_blockhash());
+	///	assert!(pwasm_ethereum::block_hash(5).is_err());
+	/// # }
+	/// ```
+	pub fn fail_blockhash(mut self) -> Self {
+		self.faults.push(Fault::BlockhashFails);
+		self
+	}
+
+	/// Runs out of gas on the `at`th (starting from 1) extern invocation
+	///
+	/// The call frame running out of gas fails: a call returns an error to its caller
+	/// and a transaction executed with `ext_transact` is reverted.
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # use pwasm_test::{ext_reset, ext_transact, Endpoint};
+	/// # fn main () {
+	/// #
+	///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e
+	///		.endpoint(contract, Endpoint::returning(Box::new(|_val, _input| {
+	///			Ok(vec![pwasm_ethereum::timestamp() as u8, pwasm_ethereum::timestamp() as u8])
+	///		})))
+	///		.out_of_gas_at(2)
+	///	);
+	///	assert!(!ext_transact(Default::default(), contract, 0.into(), &[]).status);
+	/// # }
+	/// ```
+	pub fn out_of_gas_at(mut self, at: usize) -> Self {
+		self.faults.push(Fault::OutOfGas { at: at });
+		self
+	}
+
+	/// Makes `pwasm_ethereum::read` of `key` return `value` instead of the stored word
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::H256;
+	/// # use pwasm_test::ext_reset;
+	/// # fn main () {
+	/// #
+	///	ext_reset(|e| e.storage(H256::zero(), [1; 32]).corrupt_storage_read(H256::zero(), [0xff; 32]));
+	///	assert_eq!(pwasm_ethereum::read(&H256::zero()), [0xff; 32]);
+	/// # }
+	/// ```
+	pub fn corrupt_storage_read(mut self, key: H256, value: [u8; 32]) -> Self {
+		self.faults.push(Fault::CorruptStorageRead { key: key, value: value });
+		self
+	}
+
 	/// Builds ExternalInstance from ExternalBuilder
 	pub fn build(self) -> ExternalInstance {
+		let mut storage = self.account_storage;
+		storage.entry(self.address).or_insert_with(BTreeMap::new).extend(self.storage);
 		ExternalInstance {
 			log: RefCell::new(Vec::new()),
 			calls: RefCell::new(Vec::new()),
-			storage: RefCell::new(self.storage),
+			trace: RefCell::new(Tracer::default()),
+			storage: RefCell::new(storage),
 			endpoints: self.endpoints,
-			balances: self.balances,
+			balances: RefCell::new(self.balances),
+			nonces: RefCell::new(self.nonces),
+			code: RefCell::new(self.code),
+			code_hashes: self.code_hashes,
 			sender: self.sender,
 			value: self.value,
 			origin: self.origin,
@@ -293,6 +709,13 @@ impl ExternalBuilder {
 			gas_limit: self.gas_limit,
 			blocknumber: self.blocknumber,
 			timestamp: self.timestamp,
+			block_time: self.block_time,
+			blockhashes: self.blockhashes,
+			faults: self.faults,
+			labels: self.labels,
+			signatures: self.signatures,
+			layouts: self.layouts,
+			abis: self.abis,
 		}
 	}
 
@@ -300,8 +723,13 @@ impl ExternalBuilder {
 	pub fn from_instance(instance: ExternalInstance) -> ExternalBuilder {
 		ExternalBuilder {
 			endpoints: instance.endpoints.clone(),
-			storage: instance.storage.borrow().clone(),
-			balances: instance.balances,
+			// Every account keeps its storage, so changing `address` doesn't move storage between contracts
+			storage: BTreeMap::new(),
+			account_storage: instance.storage.into_inner(),
+			balances: instance.balances.into_inner(),
+			nonces: instance.nonces.into_inner(),
+			code: instance.code.into_inner(),
+			code_hashes: instance.code_hashes,
 			sender: instance.sender,
 			value: instance.value,
 			origin: instance.origin,
@@ -311,6 +739,13 @@ impl ExternalBuilder {
 			gas_limit: instance.gas_limit,
 			blocknumber: instance.blocknumber,
 			timestamp: instance.timestamp,
+			block_time: instance.block_time,
+			blockhashes: instance.blockhashes,
+			faults: instance.faults,
+			labels: instance.labels,
+			signatures: instance.signatures,
+			layouts: instance.layouts,
+			abis: instance.abis,
 		}
 	}
 }
@@ -0,0 +1,469 @@
+//! ABI coverage: methods called, revert paths taken, events emitted and slots written per contract
+//!
+//! Every finished top-level frame is recorded in a report shared by all tests of the process.
+//! Recording is off unless `PWASM_ABI_COVERAGE` is set or `record_abi_coverage` is called.
+//! If `PWASM_ABI_COVERAGE` names a file, the report is merged into it when the process exits,
+//! so running `cargo test` aggregates coverage of every test binary into one file.
+//! Processes merging at the same time take turns holding a `.lock` file next to the report,
+//! a lock left by a process which isn't running anymore is taken over.
+//! A path ending with `.html` gets an HTML page, with the data kept next to it in `<path>.json`.
+//! Remove the file before a run to start from scratch.
+//!
+//! Methods and events of a contract declared with `ExternalBuilder::abi` are listed even if never reached.
+//!
+//! ```
+//! # extern crate pwasm_test;
+//! # use pwasm_test::{ext_reset, ext_transact, abi_coverage, record_abi_coverage, selector, Endpoint};
+//! # use pwasm_test::accounts::alice;
+//! # fn main () {
+//! #
+//!	record_abi_coverage();
+//!	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+//!	ext_reset(|e| e
+//!		.abi(token, &["pause()", "unpause()"])
+//!		.endpoint(token, Endpoint::ok())
+//!	);
+//!	ext_transact(alice(), token, 0.into(), &selector("pause()"));
+//!
+//!	let coverage = abi_coverage();
+//!	let methods = &coverage.contracts[&token].methods;
+//!	assert!(methods["pause()"].calls > 0);
+//!	assert!(methods.contains_key("unpause()"));
+//! # }
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::process;
+use std::sync::{Mutex, MutexGuard, Once, ONCE_INIT};
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libc;
+use serde_json::{self, Map, Value};
+use pwasm_std::types::Address;
+
+use abi::{Abi, Signature};
+use accounts;
+use external::LogEntry;
+use hex;
+use trace::{CallKind, TraceFrame};
+
+/// Environment variable with the path of the report written at exit
+const REPORT_PATH_VAR: &str = "PWASM_ABI_COVERAGE";
+/// Seconds to wait for another process to finish writing the report
+const LOCK_TIMEOUT_SECS: u64 = 30;
+
+/// What was reached of a single method
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MethodCoverage {
+	/// Number of frames calling the method
+	pub calls: u64,
+	/// Number of failed frames by error, e.g. `reverted` or `out of gas`
+	pub reverts: BTreeMap<String, u64>,
+	/// Number of emitted logs by event, logs of failed frames aren't counted
+	pub events: BTreeMap<String, u64>,
+	/// Storage keys written by successful frames
+	pub slots: BTreeSet<String>,
+}
+
+/// Coverage of a contract by method: signatures of known methods, hex selectors of unknown ones,
+/// `constructor` for creation and `fallback` for calls without a selector
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContractCoverage {
+	/// Label or book name of the contract, empty if it has none
+	pub name: String,
+	pub methods: BTreeMap<String, MethodCoverage>,
+	/// Number of emitted logs by event over all methods
+	pub events: BTreeMap<String, u64>,
+}
+
+/// ABI coverage of contracts by address
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AbiCoverage {
+	pub contracts: BTreeMap<Address, ContractCoverage>,
+}
+
+/// Signatures and contracts of the installed world used to name recorded frames
+#[derive(Default)]
+struct Names {
+	abis: BTreeMap<Address, Abi>,
+	signatures: Vec<Signature>,
+	/// Addresses with an endpoint, code or ABI, `None` if no `ExternalInstance` is installed
+	contracts: Option<BTreeSet<Address>>,
+}
+
+impl Names {
+	fn installed() -> Names {
+		accounts::with_instance(|instance| {
+			let mut contracts: BTreeSet<Address> = instance.endpoints.keys().cloned().collect();
+			contracts.extend(instance.abis.keys().cloned());
+			contracts.extend(instance.code_hashes.keys().cloned());
+			contracts.extend(instance.code.borrow().iter().filter(|&(_, code)| !code.is_empty()).map(|(address, _)| *address));
+			Some(Names {
+				abis: instance.abis.clone(),
+				signatures: instance.signatures.clone(),
+				contracts: Some(contracts),
+			})
+		}).unwrap_or_default()
+	}
+
+	fn is_contract(&self, address: &Address) -> bool {
+		self.contracts.as_ref().map_or(true, |contracts| contracts.contains(address))
+	}
+
+	fn method(&self, frame: &TraceFrame) -> String {
+		if frame.kind == CallKind::Create {
+			return "constructor".to_owned();
+		}
+		if frame.input.len() < 4 {
+			return "fallback".to_owned();
+		}
+		self.abis.get(&frame.to).and_then(|abi| abi.method(&frame.input))
+			.or_else(|| self.signatures.iter().find(|signature| signature.selector[..] == frame.input[..4]))
+			.map(|signature| signature.to_string())
+			.unwrap_or_else(|| hex::to_hex(&frame.input[..4]))
+	}
+
+	fn event(&self, address: &Address, log: &LogEntry) -> String {
+		let topic = match log.topics.first() {
+			Some(topic) => topic,
+			None => return "anonymous".to_owned(),
+		};
+		self.abis.get(address).and_then(|abi| abi.event(topic))
+			.or_else(|| self.abis.values().filter_map(|abi| abi.event(topic)).next())
+			.map(|event| event.to_string())
+			.unwrap_or_else(|| hex::to_hex(topic))
+	}
+}
+
+impl AbiCoverage {
+	/// Adds counts of `other`
+	pub fn merge(&mut self, other: &AbiCoverage) {
+		for (address, other) in &other.contracts {
+			let contract = self.contracts.entry(*address).or_insert_with(ContractCoverage::default);
+			if contract.name.is_empty() {
+				contract.name = other.name.clone();
+			}
+			add_counts(&mut contract.events, &other.events);
+			for (name, other) in &other.methods {
+				let method = contract.methods.entry(name.clone()).or_insert_with(MethodCoverage::default);
+				method.calls += other.calls;
+				add_counts(&mut method.reverts, &other.reverts);
+				add_counts(&mut method.events, &other.events);
+				method.slots.extend(other.slots.iter().cloned());
+			}
+		}
+	}
+
+	/// Lists every method and event declared for the installed contracts
+	fn declare(&mut self, names: &Names) {
+		for (address, abi) in &names.abis {
+			let contract = self.contract(address);
+			for method in &abi.methods {
+				contract.methods.entry(method.to_string()).or_insert_with(MethodCoverage::default);
+			}
+			for event in &abi.events {
+				contract.events.entry(event.to_string()).or_insert(0);
+			}
+		}
+	}
+
+	fn contract(&mut self, address: &Address) -> &mut ContractCoverage {
+		let contract = self.contracts.entry(*address).or_insert_with(ContractCoverage::default);
+		if contract.name.is_empty() {
+			contract.name = accounts::name_of(address).unwrap_or_default();
+		}
+		contract
+	}
+
+	fn record(&mut self, names: &Names, frame: &TraceFrame) {
+		if frame.kind == CallKind::Create || names.is_contract(&frame.to) {
+			let method = names.method(frame);
+			let contract = self.contract(&frame.to);
+			let coverage = contract.methods.entry(method).or_insert_with(MethodCoverage::default);
+			coverage.calls += 1;
+			match frame.error {
+				Some(ref error) => *coverage.reverts.entry(error.clone()).or_insert(0) += 1,
+				None => {
+					for log in &frame.logs {
+						let event = names.event(&frame.to, log);
+						*coverage.events.entry(event.clone()).or_insert(0) += 1;
+						*contract.events.entry(event).or_insert(0) += 1;
+					}
+					coverage.slots.extend(frame.storage_writes.iter().map(|write| hex::to_hex(&write.key)));
+				},
+			}
+		}
+		for call in &frame.calls {
+			self.record(names, call);
+		}
+	}
+
+	/// Parses a report written by `to_json`
+	pub fn from_json(json: &str) -> Result<AbiCoverage, String> {
+		let value: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+		let mut coverage = AbiCoverage::default();
+		for (address, contract) in object(&value["contracts"]) {
+			let address = match hex::from_hex(&address) {
+				Some(ref bytes) if bytes.len() == 20 => Address::from_slice(bytes),
+				_ => return Err(format!("{} is not an address", address)),
+			};
+			coverage.contracts.insert(address, ContractCoverage {
+				name: contract["name"].as_str().unwrap_or_default().to_owned(),
+				events: counts(&contract["events"]),
+				methods: object(&contract["methods"]).iter().map(|(name, method)| (name.clone(), MethodCoverage {
+					calls: method["calls"].as_u64().unwrap_or(0),
+					reverts: counts(&method["reverts"]),
+					events: counts(&method["events"]),
+					slots: method["slots"].as_array().map(|slots| {
+						slots.iter().filter_map(Value::as_str).map(ToOwned::to_owned).collect()
+					}).unwrap_or_default(),
+				})).collect(),
+			});
+		}
+		Ok(coverage)
+	}
+
+	/// Report as JSON: `{"contracts": {"0x..": {"name", "events", "methods": {"transfer(address,uint256)": {"calls", "reverts", "events", "slots"}}}}}`
+	pub fn to_json(&self) -> String {
+		let contracts: Map<String, Value> = self.contracts.iter().map(|(address, contract)| {
+			let methods: Map<String, Value> = contract.methods.iter().map(|(name, method)| {
+				let mut fields = Map::new();
+				fields.insert("calls".to_owned(), method.calls.into());
+				fields.insert("reverts".to_owned(), counts_to_json(&method.reverts));
+				fields.insert("events".to_owned(), counts_to_json(&method.events));
+				fields.insert("slots".to_owned(), Value::Array(method.slots.iter().map(|slot| Value::from(slot.as_str())).collect()));
+				(name.clone(), Value::Object(fields))
+			}).collect();
+			let mut fields = Map::new();
+			fields.insert("name".to_owned(), contract.name.clone().into());
+			fields.insert("events".to_owned(), counts_to_json(&contract.events));
+			fields.insert("methods".to_owned(), Value::Object(methods));
+			(hex::to_hex(address), Value::Object(fields))
+		}).collect();
+		let mut report = Map::new();
+		report.insert("contracts".to_owned(), Value::Object(contracts));
+		serde_json::to_string_pretty(&Value::Object(report)).expect("Coverage contains only strings, numbers and maps; qed")
+	}
+
+	/// Report as an HTML page with a table of methods per contract, unreached methods and events highlighted
+	pub fn to_html(&self) -> String {
+		let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>ABI coverage</title>\n<style>\n\
+			body { font-family: sans-serif; }\n\
+			table { border-collapse: collapse; margin-bottom: 2em; }\n\
+			td, th { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }\n\
+			.missed { background: #fdd; }\n\
+			.hit { background: #dfd; }\n\
+			</style>\n</head>\n<body>\n<h1>ABI coverage</h1>\n");
+		for (address, contract) in &self.contracts {
+			let covered = contract.methods.values().filter(|method| method.calls > 0).count();
+			html.push_str(&format!("<h2>{} <small>{}</small></h2>\n<p>{} of {} methods called</p>\n",
+				escape(&contract.name), hex::to_hex(address), covered, contract.methods.len()));
+			html.push_str("<table>\n<tr><th>Method</th><th>Calls</th><th>Reverts</th><th>Events</th><th>Storage written</th></tr>\n");
+			for (name, method) in &contract.methods {
+				html.push_str(&format!("<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+					if method.calls > 0 { "hit" } else { "missed" },
+					escape(name),
+					method.calls,
+					format_counts(&method.reverts),
+					format_counts(&method.events),
+					method.slots.iter().map(|slot| escape(slot)).collect::<Vec<_>>().join("<br>"),
+				));
+			}
+			html.push_str("</table>\n");
+			if !contract.events.is_empty() {
+				html.push_str("<table>\n<tr><th>Event</th><th>Emitted</th></tr>\n");
+				for (event, count) in &contract.events {
+					html.push_str(&format!("<tr class=\"{}\"><td>{}</td><td>{}</td></tr>\n",
+						if *count > 0 { "hit" } else { "missed" }, escape(event), count));
+				}
+				html.push_str("</table>\n");
+			}
+		}
+		html.push_str("</body>\n</html>\n");
+		html
+	}
+}
+
+fn add_counts(counts: &mut BTreeMap<String, u64>, other: &BTreeMap<String, u64>) {
+	for (key, count) in other {
+		*counts.entry(key.clone()).or_insert(0) += count;
+	}
+}
+
+fn counts_to_json(counts: &BTreeMap<String, u64>) -> Value {
+	Value::Object(counts.iter().map(|(key, count)| (key.clone(), Value::from(*count))).collect())
+}
+
+fn object(value: &Value) -> Map<String, Value> {
+	value.as_object().cloned().unwrap_or_default()
+}
+
+fn counts(value: &Value) -> BTreeMap<String, u64> {
+	object(value).iter().map(|(key, count)| (key.clone(), count.as_u64().unwrap_or(0))).collect()
+}
+
+fn format_counts(counts: &BTreeMap<String, u64>) -> String {
+	counts.iter().map(|(key, count)| format!("{} &times; {}", count, escape(key))).collect::<Vec<_>>().join("<br>")
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+static INIT: Once = ONCE_INIT;
+static RECORDING: AtomicBool = ATOMIC_BOOL_INIT;
+
+lazy_static! {
+	/// Report of the process, never dropped so it's still there when written at exit
+	static ref COVERAGE: Mutex<AbiCoverage> = Mutex::new(AbiCoverage::default());
+}
+
+/// Turns on recording and writing the report at exit if `PWASM_ABI_COVERAGE` is set
+fn init() {
+	INIT.call_once(|| {
+		if env::var_os(REPORT_PATH_VAR).is_some() {
+			RECORDING.store(true, Ordering::SeqCst);
+			unsafe { libc::atexit(write_at_exit); }
+		}
+	});
+}
+
+/// Report of the process
+fn global() -> MutexGuard<'static, AbiCoverage> {
+	init();
+	// A test panicking while recording doesn't make the report unusable
+	COVERAGE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Records a finished top-level frame with the frames it made, if recording is on
+pub(crate) fn record(frame: &TraceFrame) {
+	init();
+	if !RECORDING.load(Ordering::SeqCst) {
+		return;
+	}
+	let names = Names::installed();
+	let mut coverage = global();
+	coverage.declare(&names);
+	coverage.record(&names, frame);
+}
+
+/// Turns on recording of ABI coverage in this process without `PWASM_ABI_COVERAGE`,
+/// e.g. to inspect it with `abi_coverage`
+pub fn record_abi_coverage() {
+	init();
+	RECORDING.store(true, Ordering::SeqCst);
+}
+
+/// ABI coverage recorded so far by this process
+///
+/// Empty unless `PWASM_ABI_COVERAGE` is set or `record_abi_coverage` was called.
+pub fn abi_coverage() -> AbiCoverage {
+	global().clone()
+}
+
+extern "C" fn write_at_exit() {
+	if let Some(path) = env::var_os(REPORT_PATH_VAR) {
+		let path = path.to_string_lossy().into_owned();
+		if let Err(err) = write_report(&path) {
+			eprintln!("Can't write ABI coverage to {}: {}", path, err);
+		}
+	}
+}
+
+/// Lock file held while the report is read and written, so parallel test binaries don't lose each other's updates
+///
+/// The file contains the id of the holding process.
+struct ReportLock {
+	path: String,
+}
+
+impl ReportLock {
+	fn acquire(path: String) -> io::Result<ReportLock> {
+		let started = Instant::now();
+		loop {
+			match OpenOptions::new().write(true).create_new(true).open(&path) {
+				Ok(mut file) => {
+					let lock = ReportLock { path: path };
+					file.write_all(process::id().to_string().as_bytes())?;
+					return Ok(lock);
+				},
+				Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists && holder_exited(&path) => {
+					// The holder was killed before it could remove the lock
+					let _ = fs::remove_file(&path);
+				},
+				Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists && started.elapsed() < Duration::from_secs(LOCK_TIMEOUT_SECS) => {
+					thread::sleep(Duration::from_millis(10));
+				},
+				Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {
+					return Err(io::Error::new(io::ErrorKind::TimedOut,
+						format!("{} is still locked, remove it if no tests are running", path)));
+				},
+				Err(err) => return Err(err),
+			}
+		}
+	}
+}
+
+impl Drop for ReportLock {
+	fn drop(&mut self) {
+		let _ = fs::remove_file(&self.path);
+	}
+}
+
+/// Returns `true` if the lock at `path` names a process which isn't running anymore
+///
+/// A lock without an id yet is being created, so its holder counts as running.
+fn holder_exited(path: &str) -> bool {
+	let mut id = String::new();
+	if File::open(path).and_then(|mut file| file.read_to_string(&mut id)).is_err() {
+		return false;
+	}
+	id.trim().parse().map(|id| !is_running(id)).unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn is_running(id: u32) -> bool {
+	// Signal 0 only checks that the process exists, EPERM means it belongs to another user
+	unsafe { libc::kill(id as libc::pid_t, 0) == 0 } || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+#[cfg(not(unix))]
+fn is_running(_id: u32) -> bool {
+	true
+}
+
+/// Writes `contents` to a file of this process next to `path` and moves it over `path`,
+/// so readers never see a partly written report
+fn replace_file(path: &str, contents: &str) -> io::Result<()> {
+	let temporary = format!("{}.{}", path, process::id());
+	File::create(&temporary)?.write_all(contents.as_bytes())?;
+	fs::rename(&temporary, path)
+}
+
+/// Merges the report of this process into the one at `path`
+fn write_report(path: &str) -> io::Result<()> {
+	let html = path.ends_with(".html");
+	let json_path = if html { format!("{}.json", path) } else { path.to_owned() };
+	let _lock = ReportLock::acquire(format!("{}.lock", json_path))?;
+
+	let mut coverage = match File::open(&json_path) {
+		Ok(mut file) => {
+			let mut json = String::new();
+			file.read_to_string(&mut json)?;
+			AbiCoverage::from_json(&json).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+		},
+		Err(ref err) if err.kind() == io::ErrorKind::NotFound => AbiCoverage::default(),
+		Err(err) => return Err(err),
+	};
+	coverage.merge(&global());
+
+	replace_file(&json_path, &coverage.to_json())?;
+	if html {
+		replace_file(path, &coverage.to_html())?;
+	}
+	Ok(())
+}
@@ -0,0 +1,210 @@
+//! Differential testing of a natively linked contract against its `.wasm` artifact
+use std::fmt;
+use std::collections::BTreeMap;
+
+use pwasm_std::types::{H256, U256, Address};
+
+use builder::ExternalBuilder;
+use external::{Endpoint, ExternalInstance, LogEntry, Receipt};
+use externs::{ExtGuard, get_external};
+use wasm::WasmContract;
+use hex;
+use ext_transact;
+
+/// Transaction of a differential run
+#[derive(Clone, Debug)]
+pub struct Transaction {
+	pub from: Address,
+	pub value: U256,
+	pub input: Vec<u8>,
+}
+
+/// First difference between the native and the wasm run
+#[derive(Clone, Debug)]
+pub struct Divergence {
+	/// Index of the transaction after which the worlds differ
+	pub step: usize,
+	/// What differs, e.g. `output` or `storage[0x..][0x..]`
+	pub what: String,
+	pub native: String,
+	pub wasm: String,
+}
+
+impl fmt::Display for Divergence {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Native and wasm runs diverged at step #{}: {} differs\n  native: {}\n  wasm:   {}",
+			self.step, self.what, self.native, self.wasm)
+	}
+}
+
+/// Runs the same transactions against a natively linked contract and its `.wasm` artifact
+/// in two identical worlds, comparing return data, logs, storage, balances, nonces and code after every step
+///
+/// Worlds are installed only while their transactions execute, the previously installed external is restored after.
+///
+/// # Example
+/// ```no_run
+/// # extern crate pwasm_test;
+/// # use pwasm_test::{Differential, Endpoint, WasmContract};
+/// # fn main () {
+/// #
+///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///	let owner = "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap();
+///	Differential::new(contract, || Endpoint::ok(), WasmContract::load("target/pwasm/token.wasm").unwrap())
+///		.setup(move |e| e.balance_of(owner, 1000.into()))
+///		.transact(owner, 0.into(), &[0x18, 0x16, 0x0d, 0xdd])
+///		.run()
+///		.unwrap();
+/// # }
+/// ```
+pub struct Differential {
+	address: Address,
+	native: Box<Fn() -> Endpoint>,
+	wasm: WasmContract,
+	setup: Box<Fn(ExternalBuilder) -> ExternalBuilder>,
+	transactions: Vec<Transaction>,
+}
+
+impl Differential {
+	/// Creates a run for the contract at `address`,
+	/// `native` is called once per run to create the natively linked endpoint
+	pub fn new<N>(address: Address, native: N, wasm: WasmContract) -> Differential
+		where N: Fn() -> Endpoint + 'static
+	{
+		Differential {
+			address: address,
+			native: Box::new(native),
+			wasm: wasm,
+			setup: Box::new(|e: ExternalBuilder| e),
+			transactions: Vec::new(),
+		}
+	}
+
+	/// Sets up the world both contracts run in
+	pub fn setup<F>(mut self, setup: F) -> Differential
+		where F: Fn(ExternalBuilder) -> ExternalBuilder + 'static
+	{
+		self.setup = Box::new(setup);
+		self
+	}
+
+	/// Appends a transaction to the contract
+	pub fn transact(mut self, from: Address, value: U256, input: &[u8]) -> Differential {
+		self.transactions.push(Transaction {
+			from: from,
+			value: value,
+			input: input.to_vec(),
+		});
+		self
+	}
+
+	/// Runs all transactions, returns receipts of the native run if both runs are identical
+	pub fn run(&self) -> Result<Vec<Receipt>, Divergence> {
+		let mut native = (self.setup)(ExternalBuilder::new())
+			.endpoint(self.address, (self.native)())
+			.build();
+		let mut wasm = (self.setup)(ExternalBuilder::new())
+			.endpoint(self.address, self.wasm.clone().into())
+			.build();
+		let mut receipts = Vec::with_capacity(self.transactions.len());
+		for (step, tx) in self.transactions.iter().enumerate() {
+			let (native_receipt, native_world) = self.execute(native, tx);
+			let (wasm_receipt, wasm_world) = self.execute(wasm, tx);
+			compare(&native_receipt, &native_world, &wasm_receipt, &wasm_world).map_err(|mut divergence| {
+				divergence.step = step;
+				divergence
+			})?;
+			native = native_world;
+			wasm = wasm_world;
+			receipts.push(native_receipt);
+		}
+		Ok(receipts)
+	}
+
+	fn execute(&self, world: ExternalInstance, tx: &Transaction) -> (Receipt, ExternalInstance) {
+		let _guard = ExtGuard::install(Box::new(world));
+		let receipt = ext_transact(tx.from, self.address, tx.value, &tx.input);
+		(receipt, get_external::<ExternalInstance>())
+	}
+}
+
+fn compare(native: &Receipt, native_world: &ExternalInstance, wasm: &Receipt, wasm_world: &ExternalInstance) -> Result<(), Divergence> {
+	check("status", &native.status, &wasm.status)?;
+	if native.output != wasm.output {
+		return diverged("output", hex::to_hex(&native.output), hex::to_hex(&wasm.output));
+	}
+	check("number of logs", &native.logs.len(), &wasm.logs.len())?;
+	for (index, (native_log, wasm_log)) in native.logs.iter().zip(wasm.logs.iter()).enumerate() {
+		if native_log.topics != wasm_log.topics || native_log.data != wasm_log.data {
+			return diverged(&format!("logs[{}]", index), format_log(native_log), format_log(wasm_log));
+		}
+	}
+	let native_storage = native_world.storage.borrow();
+	let wasm_storage = wasm_world.storage.borrow();
+	for address in native_storage.keys().chain(wasm_storage.keys()) {
+		let empty = BTreeMap::new();
+		let native_slots = native_storage.get(address).unwrap_or(&empty);
+		let wasm_slots = wasm_storage.get(address).unwrap_or(&empty);
+		for key in native_slots.keys().chain(wasm_slots.keys()) {
+			let native_value = native_slots.get(key).cloned().unwrap_or([0u8; 32]);
+			let wasm_value = wasm_slots.get(key).cloned().unwrap_or([0u8; 32]);
+			if native_value != wasm_value {
+				return diverged(&format!("storage[{}][{}]", hex::to_hex(address), hex::to_hex(key)),
+					hex::to_hex(&native_value), hex::to_hex(&wasm_value));
+			}
+		}
+	}
+	let native_balances = native_world.balances.borrow();
+	let wasm_balances = wasm_world.balances.borrow();
+	for address in native_balances.keys().chain(wasm_balances.keys()) {
+		let native_balance = native_world.balance_of(address);
+		let wasm_balance = wasm_world.balance_of(address);
+		if native_balance != wasm_balance {
+			return diverged(&format!("balance[{}]", hex::to_hex(address)),
+				hex::u256_to_hex(&native_balance), hex::u256_to_hex(&wasm_balance));
+		}
+	}
+	let native_nonces = native_world.nonces.borrow();
+	let wasm_nonces = wasm_world.nonces.borrow();
+	for address in native_nonces.keys().chain(wasm_nonces.keys()) {
+		let native_nonce = native_world.nonce(address);
+		let wasm_nonce = wasm_world.nonce(address);
+		if native_nonce != wasm_nonce {
+			return diverged(&format!("nonce[{}]", hex::to_hex(address)),
+				hex::u256_to_hex(&native_nonce), hex::u256_to_hex(&wasm_nonce));
+		}
+	}
+	let native_code = native_world.code.borrow();
+	let wasm_code = wasm_world.code.borrow();
+	for address in native_code.keys().chain(wasm_code.keys()) {
+		let native_contract = native_world.code_at(address);
+		let wasm_contract = wasm_world.code_at(address);
+		if native_contract != wasm_contract {
+			return diverged(&format!("code[{}]", hex::to_hex(address)),
+				hex::to_hex(&native_contract), hex::to_hex(&wasm_contract));
+		}
+	}
+	Ok(())
+}
+
+fn check<T: PartialEq + fmt::Debug>(what: &str, native: &T, wasm: &T) -> Result<(), Divergence> {
+	if native == wasm {
+		Ok(())
+	} else {
+		diverged(what, format!("{:?}", native), format!("{:?}", wasm))
+	}
+}
+
+fn diverged(what: &str, native: String, wasm: String) -> Result<(), Divergence> {
+	Err(Divergence {
+		step: 0,
+		what: what.to_owned(),
+		native: native,
+		wasm: wasm,
+	})
+}
+
+fn format_log(log: &LogEntry) -> String {
+	let topics: Vec<String> = log.topics.iter().map(|topic: &H256| hex::to_hex(topic)).collect();
+	format!("topics [{}], data {}", topics.join(", "), hex::to_hex(&log.data))
+}
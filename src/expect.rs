@@ -0,0 +1,163 @@
+//! Expectations of outgoing calls checked at the end of a scope
+use std::cmp;
+
+use pwasm_std::types::{U256, Address};
+
+use abi::{self, AbiArgs, Signature};
+use external::{Call, ExternalInstance};
+use externs::try_get_external;
+use hex;
+use prelude::*;
+
+/// Expected `pwasm_ethereum::call` to an address, checked when dropped
+///
+/// Created with `ext_expect_call`. Without `times` at least one matching call is expected.
+#[must_use = "expectations are checked when dropped, bind them to a variable living until the end of the test"]
+pub struct ExpectedCall {
+	address: Address,
+	signature: Option<Signature>,
+	args: Option<Vec<u8>>,
+	value: Option<U256>,
+	times: Option<usize>,
+	/// Number of calls recorded before the expectation was created
+	start: usize,
+}
+
+impl ExpectedCall {
+	pub(crate) fn new(address: Address) -> ExpectedCall {
+		ExpectedCall {
+			address: address,
+			signature: None,
+			args: None,
+			value: None,
+			times: None,
+			start: try_get_external::<ExternalInstance>().map(|instance| instance.calls.borrow().len()).unwrap_or(0),
+		}
+	}
+
+	/// Expects input to start with the selector of `signature`, e.g. `transfer(address,uint256)`
+	///
+	/// The signature is also used to decode inputs in mismatch messages.
+	pub fn with_selector(mut self, signature: &str) -> ExpectedCall {
+		match Signature::parse(signature) {
+			Ok(signature) => self.signature = Some(signature),
+			Err(err) => panic!("{}", err),
+		}
+		self
+	}
+
+	/// Expects ABI-encoded `args` following the selector
+	pub fn with_args<A: AbiArgs>(mut self, args: A) -> ExpectedCall {
+		self.args = Some(args.encode());
+		self
+	}
+
+	/// Expects `value` to be transferred
+	pub fn with_value(mut self, value: U256) -> ExpectedCall {
+		self.value = Some(value);
+		self
+	}
+
+	/// Expects exactly `times` matching calls
+	pub fn times(mut self, times: usize) -> ExpectedCall {
+		self.times = Some(times);
+		self
+	}
+
+	/// Returns a description of the mismatch if the recorded calls don't meet the expectation
+	pub fn check(&self) -> Result<(), String> {
+		let instance = try_get_external::<ExternalInstance>().map_err(|err| err.to_string())?;
+		let calls = instance.calls();
+		let calls: Vec<&Call> = calls.iter().skip(self.start).filter(|call| call.address == self.address).collect();
+		let matching = calls.iter().filter(|call| self.mismatch(call).is_none()).count();
+		let met = match self.times {
+			Some(times) => matching == times,
+			None => matching > 0,
+		};
+		if met {
+			return Ok(());
+		}
+
+		let mut message = format!("Expected {} {}, found {} matching", match self.times {
+			Some(times) => format!("{} call(s) of", times),
+			None => "a call of".to_owned(),
+		}, self.describe(), matching);
+		if calls.is_empty() {
+			message.push_str(&format!("\nNo calls to {} were made", abi::format_address(&self.address)));
+		}
+		for (index, call) in calls.iter().enumerate() {
+			message.push_str(&format!("\n  #{} {} value {}", index, self.format_input(&call.input), call.value));
+			if let Some(mismatch) = self.mismatch(call) {
+				message.push_str(&format!(": {}", mismatch));
+			}
+		}
+		Err(message)
+	}
+
+	/// What is expected, e.g. `0x35da..4d55.transfer(0x16a0..a34e, 100) with value 0`
+	fn describe(&self) -> String {
+		let mut description = abi::format_address(&self.address);
+		match (&self.signature, &self.args) {
+			(&Some(ref signature), &Some(ref args)) => {
+				let args = signature.decode(args).map(|args| args.join(", ")).unwrap_or_else(|_| hex::to_hex(args));
+				description.push_str(&format!(".{}({})", signature.name, args));
+			},
+			(&Some(ref signature), &None) => description.push_str(&format!(".{}({})", signature.name, signature.params.join(","))),
+			(&None, &Some(ref args)) => description.push_str(&format!(" with args {}", hex::to_hex(args))),
+			(&None, &None) => {},
+		}
+		if let Some(value) = self.value {
+			description.push_str(&format!(" with value {}", value));
+		}
+		description
+	}
+
+	fn format_input(&self, input: &[u8]) -> String {
+		match self.signature {
+			Some(ref signature) => signature.format_input(input),
+			None => hex::to_hex(input),
+		}
+	}
+
+	/// Returns why `call` doesn't match, `None` if it does
+	fn mismatch(&self, call: &Call) -> Option<String> {
+		if let Some(ref signature) = self.signature {
+			if call.input.len() < 4 || call.input[..4] != signature.selector {
+				return Some(format!("selector is not {}", hex::to_hex(&signature.selector)));
+			}
+		}
+		if let Some(ref args) = self.args {
+			let actual = if call.input.len() < 4 { &[][..] } else { &call.input[4..] };
+			if actual != &args[..] {
+				let position = actual.iter().zip(args.iter()).position(|(a, e)| a != e).unwrap_or(cmp::min(actual.len(), args.len()));
+				return Some(format!("args differ from byte {}", position));
+			}
+		}
+		match self.value {
+			Some(value) if value != call.value => Some(format!("value is not {}", value)),
+			_ => None,
+		}
+	}
+}
+
+impl Drop for ExpectedCall {
+	fn drop(&mut self) {
+		if panicking() {
+			return;
+		}
+		if let Err(message) = self.check() {
+			panic!("{}", message);
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+fn panicking() -> bool {
+	::std::thread::panicking()
+}
+
+/// Without `std` a failed test aborts, so there is no unwinding to wait for
+#[cfg(not(feature = "std"))]
+fn panicking() -> bool {
+	false
+}
@@ -1,50 +1,128 @@
 use std::any::Any;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::BTreeMap;
 use std::rc::Rc;
-use std::ops::DerefMut;
 use std::cell::RefCell;
 
 use pwasm_std::types::{H256, U256, Address};
 use pwasm_abi::eth::EndpointInterface;
+use tiny_keccak::keccak256;
+
+use trace::{CallKind, Context, TraceFrame, Tracer};
+#[cfg(feature = "std")]
+use trace::UnwindPrinter;
+use fault::{self, Faults};
+use abi::{Abi, Signature};
+use slots::Word;
+use layout::Layout;
+use externs::with_external;
+use hex;
+use prelude::*;
 
 #[doc(hidden)]
 #[derive(Debug)]
 pub struct Error;
 
+/// Size of the scratch buffer given to `Endpoint::new` closures when they are invoked
+/// as a transaction target, where the caller doesn't provide one
+const RAW_OUTPUT_SIZE: usize = 32;
+
 /// A fake contract endpoint
 /// Endpoint is just a closure which receives `value: U256`, `input: &[u8]`, `output: &mut [u8]`
 /// and returns `Ok(())` if call was successfull or `Err(Error)` otherwise
-pub struct Endpoint (Box<FnMut(U256, &[u8], &mut [u8]) -> Result<(), Error>>);
+///
+/// Closures are `Fn` so an endpoint can be re-entered, e.g. when A calls B which calls A again.
+/// Endpoints with state keep it in a `Cell` or `RefCell` of their own.
+/// Clones share the closure.
+#[derive(Clone)]
+pub struct Endpoint (Handler);
+
+#[derive(Clone)]
+enum Handler {
+	Raw(Rc<Fn(U256, &[u8], &mut [u8]) -> Result<(), Error>>),
+	Returning(Rc<Fn(U256, &[u8]) -> Result<Vec<u8>, Error>>),
+	/// Contract dispatched with `EndpointInterface`, its output has to fill the caller's buffer exactly
+	Interface(Rc<Fn(&[u8]) -> Vec<u8>>),
+}
 
 impl Endpoint {
-	pub fn new(f: Box<FnMut(U256, &[u8], &mut [u8]) -> Result<(), Error>>) -> Endpoint {
-		Endpoint(f)
+	pub fn new(f: Box<Fn(U256, &[u8], &mut [u8]) -> Result<(), Error>>) -> Endpoint {
+		Endpoint(Handler::Raw(Rc::from(f)))
+	}
+	/// Creates an endpoint from a closure which returns the output instead of writing it to a buffer
+	/// Output is truncated to the size of the caller's buffer
+	pub fn returning(f: Box<Fn(U256, &[u8]) -> Result<Vec<u8>, Error>>) -> Endpoint {
+		Endpoint(Handler::Returning(Rc::from(f)))
 	}
 	pub fn ok() -> Endpoint {
-		Endpoint(Box::new(move |_, _, _| {
+		Endpoint::new(Box::new(move |_, _, _| {
             Ok(())
         }))
 	}
 	pub fn err() -> Endpoint {
-		Endpoint(Box::new(move |_, _, _| {
+		Endpoint::new(Box::new(move |_, _, _| {
             Err(Error)
         }))
 	}
+
+	/// Invokes endpoint writing output to the `result` buffer
+	pub fn call(&self, val: U256, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		match self.0 {
+			Handler::Raw(ref f) => (**f)(val, input, result),
+			Handler::Returning(ref f) => {
+				let output = (**f)(val, input)?;
+				let len = cmp::min(output.len(), result.len());
+				result[..len].copy_from_slice(&output[..len]);
+				Ok(())
+			},
+			Handler::Interface(ref f) => {
+				let output = (**f)(input);
+				if output.len() != result.len() {
+					panic!("Contract returned {} bytes, but the caller's output buffer has {} bytes", output.len(), result.len());
+				}
+				result.copy_from_slice(&output);
+				Ok(())
+			},
+		}
+	}
+
+	/// Invokes endpoint and returns its output
+	///
+	/// There is no caller's buffer for endpoints created with `Endpoint::new`,
+	/// they write to a scratch word which is dropped and their output is empty.
+	pub fn invoke(&self, val: U256, input: &[u8]) -> Result<Vec<u8>, Error> {
+		match self.0 {
+			Handler::Raw(ref f) => {
+				let mut scratch = [0u8; RAW_OUTPUT_SIZE];
+				(**f)(val, input, &mut scratch)?;
+				Ok(Vec::new())
+			},
+			Handler::Returning(ref f) => (**f)(val, input),
+			Handler::Interface(ref f) => Ok((**f)(input)),
+		}
+	}
 }
 
 /// Wraps any `pwasm_abi::eth::EndpointInterface` to `Endpoint`
+///
+/// `EndpointInterface::dispatch` takes `&mut self`, so a contract wrapped this way can't be re-entered.
 impl<T: EndpointInterface + 'static> From<T> for Endpoint {
-	fn from(mut intf: T) -> Endpoint {
-        Endpoint(Box::new(move |_val, input, result| {
-            result.copy_from_slice(&intf.dispatch(input));
-            Ok(())
-        }))
+	fn from(intf: T) -> Endpoint {
+		let intf = RefCell::new(intf);
+		Endpoint(Handler::Interface(Rc::new(move |input| {
+			match intf.try_borrow_mut() {
+				Ok(mut intf) => intf.dispatch(input),
+				Err(_) => panic!("Contract dispatched with `EndpointInterface` is re-entered, wrap it in an `Endpoint::returning` closure with its own state instead"),
+			}
+		})))
 	}
 }
 
 #[doc(hidden)]
 /// Trait to manage calls to blockchain externs locally
 /// This trait methods are called by `pwasm_ethereum::*` externs, see `externs.rs`
+/// Methods may be re-entered by nested calls and the installed external may be replaced while they run,
+/// so implementations needing mutation should keep borrows of their `RefCell`s short
 pub trait External {
 
 	/// Invoked when contract is calling `pwasm_ethereum::balance`
@@ -162,16 +240,67 @@ pub struct Call {
 pub struct LogEntry {
 	pub topics: Box<[H256]>,
 	pub data: Box<[u8]>,
+	block: u64,
+}
+
+impl LogEntry {
+	/// Creates a log entry fired in block 0, e.g. to compare with entries of a world which wasn't mined
+	pub fn new(topics: &[H256], data: &[u8]) -> LogEntry {
+		LogEntry {
+			topics: Box::from(topics),
+			data: Box::from(data),
+			block: 0,
+		}
+	}
+
+	/// Returns number of the block the entry was fired in
+	pub fn block(&self) -> u64 {
+		self.block
+	}
+}
+
+/// State of the world when a call frame was entered
+pub(crate) struct Snapshot {
+	storage: BTreeMap<Address, BTreeMap<H256, [u8; 32]>>,
+	balances: BTreeMap<Address, U256>,
+	nonces: BTreeMap<Address, U256>,
+	code: BTreeMap<Address, Vec<u8>>,
+	logs: usize,
+}
+
+/// Outcome of a transaction executed with `ext_transact`
+#[derive(Clone, Default, Debug)]
+pub struct Receipt {
+	/// Number of the block the transaction was executed in
+	pub block: u64,
+	/// `true` if the target succeeded and its state changes were committed
+	pub status: bool,
+	/// Data returned by the target endpoint
+	pub output: Vec<u8>,
+	/// Log entries fired during the transaction, empty if it was reverted
+	pub logs: Vec<LogEntry>,
+	/// Intrinsic gas of the transaction: the base cost plus the cost of its input
+	///
+	/// Execution of endpoints isn't metered, so this is a lower bound of what a node would charge.
+	pub gas_used: u64,
+	/// Calls made by the target during the transaction
+	pub calls: Vec<Call>,
+	/// Frame of the transaction with all nested calls
+	pub trace: TraceFrame,
 }
 
 #[doc(hidden)]
 #[derive(Clone, Default)]
 pub struct ExternalInstance {
-	pub storage: RefCell<HashMap<H256, [u8; 32]>>,
+	pub(crate) storage: RefCell<BTreeMap<Address, BTreeMap<H256, [u8; 32]>>>,
 	pub calls: RefCell<Vec<Call>>,
 	pub log: RefCell<Vec<LogEntry>>,
-	pub balances: HashMap<Address, U256>,
-	pub endpoints: HashMap<Address, Rc<RefCell<Endpoint>>>,
+	pub trace: RefCell<Tracer>,
+	pub(crate) balances: RefCell<BTreeMap<Address, U256>>,
+	pub(crate) nonces: RefCell<BTreeMap<Address, U256>>,
+	pub(crate) code: RefCell<BTreeMap<Address, Vec<u8>>>,
+	pub(crate) code_hashes: BTreeMap<Address, H256>,
+	pub(crate) endpoints: BTreeMap<Address, Endpoint>,
 	pub sender: Address,
 	pub value: U256,
 	pub address: Address,
@@ -181,10 +310,141 @@ pub struct ExternalInstance {
 	pub gas_limit: U256,
 	pub blocknumber: u64,
 	pub timestamp: u64,
+	pub block_time: u64,
+	pub blockhashes: BTreeMap<u64, H256>,
+	pub faults: Faults,
+	pub labels: BTreeMap<Address, String>,
+	pub signatures: Vec<Signature>,
+	pub layouts: BTreeMap<Address, Layout>,
+	pub abis: BTreeMap<Address, Abi>,
 }
 
 impl ExternalInstance {
 
+	/// Returns nonce of some address
+	pub fn nonce(&self, address: &Address) -> U256 {
+		self.nonces.borrow().get(address).cloned().unwrap_or_default()
+	}
+
+	/// Returns balance of some address, zero for unknown accounts
+	pub fn balance_of(&self, address: &Address) -> U256 {
+		self.balances.borrow().get(address).cloned().unwrap_or_default()
+	}
+
+	/// Returns balances of all accounts known to the world
+	pub fn balances(&self) -> BTreeMap<Address, U256> {
+		self.balances.borrow().clone()
+	}
+
+	/// Returns all storage values of the contract at `address`
+	pub fn account_storage(&self, address: &Address) -> BTreeMap<H256, [u8; 32]> {
+		self.storage.borrow().get(address).cloned().unwrap_or_default()
+	}
+
+	/// Returns code deployed at `address`, empty for accounts without code
+	///
+	/// Constructors aren't executed, so for contracts made with `pwasm_ethereum::create`
+	/// this is the init code passed to it rather than the code a constructor would return.
+	pub fn code_at(&self, address: &Address) -> Vec<u8> {
+		self.code.borrow().get(address).cloned().unwrap_or_default()
+	}
+
+	/// Returns code hash of `address`, either set with `ExternalBuilder::code_hash` or keccak of its code
+	pub fn code_hash(&self, address: &Address) -> H256 {
+		match self.code_hashes.get(address) {
+			Some(hash) => *hash,
+			None => H256::from_slice(&keccak256(&self.code_at(address))),
+		}
+	}
+
+	/// Returns `true` if `address` has code, a code hash or an endpoint
+	pub fn is_contract(&self, address: &Address) -> bool {
+		self.endpoints.contains_key(address)
+			|| self.code_hashes.contains_key(address)
+			|| self.code.borrow().get(address).map_or(false, |code| !code.is_empty())
+	}
+
+	/// Increments nonce of `address` returning the previous one
+	pub fn bump_nonce(&self, address: &Address) -> U256 {
+		let mut nonces = self.nonces.borrow_mut();
+		let nonce = nonces.entry(*address).or_insert_with(U256::zero);
+		let previous = *nonce;
+		*nonce = previous + U256::one();
+		previous
+	}
+
+	/// Returns storage value for some `key` of the contract at `address`
+	pub fn storage_at(&self, address: &Address, key: &H256) -> [u8; 32] {
+		self.storage.borrow().get(address).and_then(|s| s.get(key).cloned()).unwrap_or([0u8; 32])
+	}
+
+	/// Returns storage value for some `key` of the current contract decoded as `T`
+	pub fn storage_value<T: Word>(&self, key: &H256) -> T {
+		T::from_word(&self.storage_at(&self.context().address, key))
+	}
+
+	/// Returns storage value for some `key` of the current contract as a number
+	pub fn storage_u256(&self, key: &H256) -> U256 {
+		self.storage_value(key)
+	}
+
+	/// Returns storage value for some `key` of the current contract as a right-aligned address
+	pub fn storage_address(&self, key: &H256) -> Address {
+		self.storage_value(key)
+	}
+
+	/// Returns `true` if the lowest byte of storage value for some `key` of the current contract isn't zero
+	pub fn storage_bool(&self, key: &H256) -> bool {
+		self.storage_value(key)
+	}
+
+	/// Sets storage value for some `key` of the current contract encoded as a word
+	///
+	/// Changes only this instance, an instance returned by `ext_get` is a copy of the installed one:
+	/// use `ext_set_storage_value` to change the installed world.
+	pub fn set_storage_value<T: Word>(&self, key: &H256, value: &T) {
+		self.storage.borrow_mut().entry(self.context().address).or_insert_with(BTreeMap::new).insert(*key, value.to_word());
+	}
+
+	/// Sets storage value for some `key` of the current contract to a number
+	pub fn set_storage_u256(&self, key: &H256, value: U256) {
+		self.set_storage_value(key, &value)
+	}
+
+	/// Sets storage value for some `key` of the current contract to a right-aligned address
+	pub fn set_storage_address(&self, key: &H256, value: Address) {
+		self.set_storage_value(key, &value)
+	}
+
+	/// Sets storage value for some `key` of the current contract to `0` or `1`
+	pub fn set_storage_bool(&self, key: &H256, value: bool) {
+		self.set_storage_value(key, &value)
+	}
+
+	/// Seals `blocks` blocks recording their hashes, each one moving timestamp by `block_time`
+	///
+	/// Panics if the block number or the timestamp overflows `u64`.
+	pub fn mine(&mut self, blocks: u64) {
+		for _ in 0..blocks {
+			let hash = block_hash(self.blocknumber, self.timestamp);
+			self.blockhashes.insert(self.blocknumber, hash);
+			let number = self.blocknumber;
+			self.blocknumber = number.checked_add(1)
+				.unwrap_or_else(|| panic!("Block number overflows after block {}", number));
+			let block_time = self.block_time;
+			self.advance_time(block_time);
+		}
+	}
+
+	/// Moves timestamp forward by `secs` without sealing a block
+	///
+	/// Panics if the timestamp overflows `u64`.
+	pub fn advance_time(&mut self, secs: u64) {
+		let timestamp = self.timestamp;
+		self.timestamp = timestamp.checked_add(secs)
+			.unwrap_or_else(|| panic!("Timestamp {} overflows when moved forward by {} seconds", timestamp, secs));
+	}
+
 	/// Returns records of calls was done via `pwasm_ethereum::call` with address, value, gas, and provided input
 	pub fn calls(&self) -> Vec<Call> {
 		self.calls.borrow().clone()
@@ -193,84 +453,269 @@ impl ExternalInstance {
 	pub fn logs(&self) -> Vec<LogEntry> {
 		self.log.borrow().clone()
 	}
+
+	/// Returns traces of finished transactions and top-level calls
+	pub fn traces(&self) -> Vec<TraceFrame> {
+		self.trace.borrow().frames().to_vec()
+	}
+
+	/// Context of the innermost call frame
+	fn context(&self) -> Context {
+		self.trace.borrow().context().unwrap_or(Context {
+			sender: self.sender,
+			address: self.address,
+			value: self.value,
+		})
+	}
+
+	/// Copies the state a failed frame has to restore
+	pub(crate) fn snapshot(&self) -> Snapshot {
+		Snapshot {
+			storage: self.storage.borrow().clone(),
+			balances: self.balances.borrow().clone(),
+			nonces: self.nonces.borrow().clone(),
+			code: self.code.borrow().clone(),
+			logs: self.log.borrow().len(),
+		}
+	}
+
+	/// Drops storage, balance, nonce, code and log changes made since `snapshot`
+	pub(crate) fn revert(&self, snapshot: Snapshot) {
+		*self.storage.borrow_mut() = snapshot.storage;
+		*self.balances.borrow_mut() = snapshot.balances;
+		*self.nonces.borrow_mut() = snapshot.nonces;
+		*self.code.borrow_mut() = snapshot.code;
+		self.log.borrow_mut().truncate(snapshot.logs);
+	}
+
+	/// Invokes the endpoint at `address` inside a new frame executing in `context`
+	///
+	/// Changes made by the frame are reverted if it fails.
+	fn enter(&self, kind: CallKind, context: Context, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		let from = self.context().address;
+		let frame = TraceFrame::new(kind, from, *address, context.value, gas, input);
+		#[cfg(feature = "std")]
+		let _printer = UnwindPrinter::new(&self.trace.borrow());
+		self.trace.borrow_mut().enter(context, frame);
+		let snapshot = self.snapshot();
+		let endpoint = self.endpoints.get(address).cloned();
+		let (outcome, error) = match endpoint {
+			Some(endpoint) => match fault::catch_out_of_gas(|| endpoint.call(context.value, input, result)) {
+				Some(outcome) => {
+					let error = outcome.as_ref().err().map(|_| "reverted".to_owned());
+					(outcome, error)
+				},
+				None => (Err(Error), Some("out of gas".to_owned())),
+			},
+			None => (Err(Error), Some(format!("no endpoint at {}", hex::to_hex(address)))),
+		};
+		// The endpoint may have replaced the installed instance, e.g. with `ext_update`
+		with_external(|installed| {
+			let installed = installed.as_any().downcast_ref::<ExternalInstance>().unwrap_or(self);
+			if error.is_some() {
+				installed.revert(snapshot);
+			}
+			installed.trace.borrow_mut().exit(result, error);
+		});
+		outcome
+	}
 }
 
 impl External for ExternalInstance {
 	fn storage_read(&self, key: &H256) -> [u8; 32] {
-		if let Some(value) = self.storage.borrow().get(key) {
-			value.clone()
-		} else {
-			[0u8; 32]
+		self.faults.extern_invoked();
+		match self.faults.corrupt_read(key) {
+			Some(value) => value,
+			None => self.storage_at(&self.context().address, key),
 		}
 	}
 
 	fn balance(&self, address: &Address) -> U256 {
-		self.balances[address]
+		self.faults.extern_invoked();
+		self.balance_of(address)
 	}
 
 	fn storage_write(&self, key: &H256, value: &[u8; 32]) {
-		self.storage.borrow_mut().insert(*key, value.clone());
+		self.faults.extern_invoked();
+		let address = self.context().address;
+		self.storage.borrow_mut().entry(address).or_insert_with(BTreeMap::new).insert(*key, value.clone());
+		self.trace.borrow_mut().storage_write(address, *key, *value);
+	}
+
+	/// Deploys `code` as is, the constructor isn't run and the init code becomes the contract code
+	fn create(&self, endowment: U256, code: &[u8]) -> Result<Address, Error> {
+		self.faults.extern_invoked();
+		let creator = self.context().address;
+		let address = contract_address(&creator, &self.nonce(&creator));
+		let creator_balance = self.balance_of(&creator);
+		let frame = TraceFrame::new(CallKind::Create, creator, address, endowment, 0, code);
+		let context = Context {
+			sender: creator,
+			address: address,
+			value: endowment,
+		};
+		self.trace.borrow_mut().enter(context, frame);
+		let error = if creator_balance < endowment {
+			Some("insufficient balance for endowment")
+		} else if self.faults.create_fails() {
+			Some("injected fault")
+		} else if self.is_contract(&address) {
+			Some("contract address collision")
+		} else {
+			None
+		};
+		if let Some(error) = error {
+			self.trace.borrow_mut().exit(&[], Some(error.to_owned()));
+			return Err(Error);
+		}
+		self.bump_nonce(&creator);
+		let balance = self.balance_of(&address);
+		{
+			let mut balances = self.balances.borrow_mut();
+			balances.insert(creator, creator_balance - endowment);
+			balances.insert(address, balance + endowment);
+		}
+		self.code.borrow_mut().insert(address, code.to_vec());
+		self.trace.borrow_mut().exit(code, None);
+		Ok(address)
 	}
 
 	fn call(&self, gas: u64, address: &Address, val: U256, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		self.faults.extern_invoked();
 		self.calls.borrow_mut().push(Call {
 			gas: gas,
 			address: address.clone(),
 			value: val,
 			input: Box::from(input)
 		});
-		if let Some(endpoint) = self.endpoints.get(address) {
-			endpoint.borrow_mut().deref_mut().0(val, input, result)
-		} else {
-			Err(Error)
+		let context = Context {
+			sender: self.context().address,
+			address: *address,
+			value: val,
+		};
+		if self.faults.call_fails(address) {
+			let frame = TraceFrame::new(CallKind::Call, context.sender, *address, val, gas, input);
+			let mut trace = self.trace.borrow_mut();
+			trace.enter(context, frame);
+			trace.exit(&[], Some("injected fault".to_owned()));
+			return Err(Error);
 		}
+		self.enter(CallKind::Call, context, gas, address, input, result)
+	}
+
+	fn call_code(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		self.faults.extern_invoked();
+		let context = self.context();
+		self.enter(CallKind::Delegate, context, gas, address, input, result)
+	}
+
+	fn static_call(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		self.faults.extern_invoked();
+		let context = Context {
+			sender: self.context().address,
+			address: *address,
+			value: U256::zero(),
+		};
+		self.enter(CallKind::Static, context, gas, address, input, result)
 	}
 
 	fn elog(&self, topics: &[H256], data: &[u8]) {
-		self.log.borrow_mut().push(LogEntry {
+		self.faults.extern_invoked();
+		let entry = LogEntry {
 			topics: Box::from(topics),
-			data: Box::from(data)
-			}
-		);
+			data: Box::from(data),
+			block: self.blocknumber,
+		};
+		self.log.borrow_mut().push(entry.clone());
+		self.trace.borrow_mut().log(entry);
+	}
+
+	fn blockhash(&self, number: u64) -> Result<H256, Error> {
+		self.faults.extern_invoked();
+		if self.faults.blockhash_fails() {
+			return Err(Error);
+		}
+		self.blockhashes.get(&number).cloned().ok_or(Error)
 	}
 
 	fn sender(&self) -> Address {
-		self.sender
+		self.faults.extern_invoked();
+		self.context().sender
 	}
 
 	fn coinbase(&self) -> Address {
+		self.faults.extern_invoked();
 		self.coinbase
 	}
 
 	fn timestamp(&self) -> u64 {
+		self.faults.extern_invoked();
 		self.timestamp
 	}
 
 	fn blocknumber(&self) -> u64 {
+		self.faults.extern_invoked();
 		self.blocknumber
 	}
 
 	fn difficulty(&self) -> U256 {
+		self.faults.extern_invoked();
 		self.difficulty
 	}
 
 	fn gas_limit(&self) -> U256 {
+		self.faults.extern_invoked();
 		self.gas_limit
 	}
 
 	fn origin(&self) -> Address {
-		self.origin
+		self.faults.extern_invoked();
+		self.trace.borrow().origin().unwrap_or(self.origin)
 	}
 
 	fn value(&self) -> U256 {
-		self.value
+		self.faults.extern_invoked();
+		self.context().value
 	}
 
 	fn address(&self) -> Address {
-		self.address
+		self.faults.extern_invoked();
+		self.context().address
 	}
 
 	fn as_any(&self) -> &Any {
 		self
 	}
 }
+
+/// Address of a contract created by `creator` with `nonce`: keccak of `rlp([creator, nonce])`
+pub fn contract_address(creator: &Address, nonce: &U256) -> Address {
+	let mut nonce_bytes = [0u8; 32];
+	nonce.to_big_endian(&mut nonce_bytes);
+	let start = nonce_bytes.iter().position(|b| *b != 0).unwrap_or(32);
+	let nonce_bytes = &nonce_bytes[start..];
+
+	let mut payload = Vec::with_capacity(55);
+	payload.push(0x80 + 20);
+	payload.extend_from_slice(creator);
+	match nonce_bytes.len() {
+		1 if nonce_bytes[0] < 0x80 => payload.push(nonce_bytes[0]),
+		len => {
+			payload.push(0x80 + len as u8);
+			payload.extend_from_slice(nonce_bytes);
+		},
+	}
+	let mut rlp = vec![0xc0 + payload.len() as u8];
+	rlp.extend(payload);
+	Address::from_slice(&keccak256(&rlp)[12..])
+}
+
+/// Deterministic hash of a mocked block
+fn block_hash(number: u64, timestamp: u64) -> H256 {
+	let mut header = [0u8; 16];
+	for i in 0..8 {
+		header[7 - i] = (number >> (i * 8)) as u8;
+		header[15 - i] = (timestamp >> (i * 8)) as u8;
+	}
+	H256::from_slice(&keccak256(&header))
+}
@@ -1,41 +1,173 @@
 //! Module for linking test externals
 use std::any::Any;
-use std::cell::{RefCell, Ref};
+use std::cell::RefCell;
+use std::{fmt, mem};
+#[cfg(feature = "std")]
+use std::error;
+use std::rc::Rc;
 use std::slice;
 use std::ptr;
 
 use pwasm_std::types::{H256, U256, Address};
 use external::{External, ExternalInstance};
+use prelude::*;
 
-thread_local!(#[doc(hidden)] pub static EXTERNAL: RefCell<Box<External>> = RefCell::new(Box::new(ExternalInstance::default())));
+#[cfg(feature = "std")]
+thread_local!(#[doc(hidden)] pub static EXTERNAL: RefCell<Rc<External>> = RefCell::new(default_external()));
+
+/// Global installed external of `no_std` builds, which run single-threaded in a Wasm runtime
+#[cfg(not(feature = "std"))]
+struct Global(RefCell<Option<Rc<External>>>);
+
+// `RefCell` and `Rc` aren't thread safe, but wasm32 has a single thread
+// so the global is never accessed concurrently
+#[cfg(all(not(feature = "std"), target_arch = "wasm32"))]
+unsafe impl Sync for Global {}
+
+#[cfg(all(not(feature = "std"), not(target_arch = "wasm32")))]
+compile_error!("Builds without `std` are only supported on wasm32, which has no threads to share the global external");
+
+#[cfg(not(feature = "std"))]
+static EXTERNAL: Global = Global(RefCell::new(None));
+
+fn default_external() -> Rc<External> {
+	Rc::new(ExternalInstance::default())
+}
+
+/// Returns the installed external without keeping the global cell borrowed
+#[cfg(feature = "std")]
+pub(crate) fn installed_external() -> Result<Rc<External>, ExtError> {
+	EXTERNAL.try_with(|e| e.try_borrow().map(|e| e.clone()).map_err(|_| ExtError::Borrowed))
+		.unwrap_or(Err(ExtError::Borrowed))
+}
+
+/// Returns the installed external without keeping the global cell borrowed
+#[cfg(not(feature = "std"))]
+pub(crate) fn installed_external() -> Result<Rc<External>, ExtError> {
+	let mut installed = EXTERNAL.0.try_borrow_mut().map_err(|_| ExtError::Borrowed)?;
+	Ok(installed.get_or_insert_with(default_external).clone())
+}
+
+/// Installs `ext`, returns the previously installed external
+///
+/// Returns `None` if the thread-local is already destroyed, e.g. when called from another thread-local's destructor.
+#[cfg(feature = "std")]
+fn replace_external(ext: Rc<External>) -> Option<Rc<External>> {
+	EXTERNAL.try_with(|e| mem::replace(&mut *e.borrow_mut(), ext)).ok()
+}
+
+/// Installs `ext`, returns the previously installed external
+#[cfg(not(feature = "std"))]
+fn replace_external(ext: Rc<External>) -> Option<Rc<External>> {
+	let previous = mem::replace(&mut *EXTERNAL.0.borrow_mut(), Some(ext));
+	Some(previous.unwrap_or_else(default_external))
+}
 
 #[doc(hidden)]
 /// Set handling external for the current thread
 /// Ideally should be done before each test to avoid dirty state
 /// Macro `test_with_external` uses this function and can help with such setup
 pub fn set_external(ext: Box<External>) {
-	EXTERNAL.with(|e| {
-		*e.borrow_mut() = ext;
-	});
+	replace_external(Rc::from(ext));
+}
+
+/// Runs `f` with the installed external
+///
+/// The global cell isn't borrowed while `f` runs, so endpoints invoked by `f` may call externs again
+/// or replace the external. The replaced external lives until the calls running on it return.
+pub(crate) fn with_external<T, F: FnOnce(&External) -> T>(f: F) -> T {
+	let external = installed_external()
+		.expect("pwasm_ethereum externs can't be called while the external is being replaced");
+	f(&*external)
+}
+
+/// Runs `f` with the installed `ExternalInstance` without copying it
+///
+/// # Panics
+///
+/// Panics if another external is installed.
+pub(crate) fn with_external_instance<T, F: FnOnce(&ExternalInstance) -> T>(f: F) -> T {
+	with_external(|external| match external.as_any().downcast_ref::<ExternalInstance>() {
+		Some(instance) => f(instance),
+		None => panic!("{}", ExtError::WrongType),
+	})
 }
 
 #[doc(hidden)]
 pub fn get_external<T: External + Clone + 'static>() -> T {
-	EXTERNAL.with(|arg| {
-		let ref_cell: &RefCell<Box<External>> = arg;
-		let ref_: Ref<Box<External>> = ref_cell.borrow();
+	match try_get_external() {
+		Ok(external) => external,
+		Err(err) => panic!("{}", err),
+	}
+}
 
-		let any: &Any = ref_.as_any();
-		let downcasted: &T = any.downcast_ref().unwrap();
-		downcasted.clone()
-	})
+/// Error of a typed access to the installed external
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtError {
+	/// Installed external is not of the requested type
+	WrongType,
+	/// Installed external is being replaced
+	Borrowed,
+}
+
+impl fmt::Display for ExtError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ExtError::WrongType => write!(f, "Installed external is not of the requested type"),
+			ExtError::Borrowed => write!(f, "Installed external is being replaced"),
+		}
+	}
 }
+
+#[cfg(feature = "std")]
+impl error::Error for ExtError {
+	fn description(&self) -> &str {
+		"external access error"
+	}
+}
+
+/// Returns a copy of the installed external, or an error if it isn't a `T`
+pub fn try_get_external<T: External + Clone + 'static>() -> Result<T, ExtError> {
+	let external = installed_external()?;
+	let any: &Any = external.as_any();
+	any.downcast_ref::<T>().cloned().ok_or(ExtError::WrongType)
+}
+
+/// Scope of an installed external, the previously installed one is restored when the guard is dropped
+///
+/// Guards can be nested and are dropped in reverse order of creation, see `ext_scope`.
+pub struct ExtGuard {
+	previous: Option<Rc<External>>,
+}
+
+impl ExtGuard {
+	/// Installs `ext` for the current thread until the guard is dropped
+	pub fn install(ext: Box<External>) -> ExtGuard {
+		ExtGuard {
+			previous: replace_external(Rc::from(ext)),
+		}
+	}
+
+	/// Returns a copy of the installed external, or an error if it isn't a `T`
+	pub fn get<T: External + Clone + 'static>(&self) -> Result<T, ExtError> {
+		try_get_external()
+	}
+}
+
+impl Drop for ExtGuard {
+	fn drop(&mut self) {
+		if let Some(previous) = self.previous.take() {
+			replace_external(previous);
+		}
+	}
+}
+
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn storage_read(key: *const u8, dst: *mut u8) {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let key = slice::from_raw_parts(key, 32);
-		let result = r.borrow_mut().storage_read(&H256::from_slice(key));
+		let result = e.storage_read(&H256::from_slice(key));
 		ptr::copy(result.as_ptr(), dst, result.len());
 	})
 }
@@ -43,22 +175,22 @@ pub unsafe extern "C" fn storage_read(key: *const u8, dst: *mut u8) {
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn storage_write(key: *const u8, src_raw: *const u8) {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let key = slice::from_raw_parts(key, 32);
 		let mut src = [0u8; 32];
 		let src_slice = slice::from_raw_parts(src_raw, 32);
 		src.copy_from_slice(src_slice);
-		r.borrow().storage_write(&H256::from_slice(key), &src);
+		e.storage_write(&H256::from_slice(key), &src);
 	})
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn create(endowment_ptr: *const u8, code_ptr: *const u8, code_len: u32, address_ptr: *mut u8) -> i32 {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let endowment = U256::from_big_endian(slice::from_raw_parts(endowment_ptr, 32));
 		let code: &[u8] = slice::from_raw_parts(code_ptr, code_len as usize);
-		match r.borrow().create(endowment, code) {
+		match e.create(endowment, code) {
 			Ok(result) => { ptr::copy(result.as_ptr(), address_ptr, Address::len_bytes()); 0 },
 			Err(_e) => 1
 		}
@@ -77,12 +209,12 @@ pub unsafe extern "C" fn ccall(
 	result_len: u32,
 ) -> i32
 {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let address = Address::from_slice(slice::from_raw_parts(address_ptr, 20));
 		let val = U256::from_big_endian(slice::from_raw_parts(val_ptr, 32));
 		let input: &[u8] = slice::from_raw_parts(input_ptr, input_len as usize);
 		let result: &mut[u8] = slice::from_raw_parts_mut(result_ptr, result_len as usize);
-		match r.borrow().call(gas, &address, val, input, result) {
+		match e.call(gas, &address, val, input, result) {
 			Ok(_r) => 0,
 			Err(_e) => 1
 		}
@@ -100,11 +232,11 @@ pub unsafe extern "C" fn dcall(
 	result_len: u32,
 ) -> i32
 {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let address = Address::from_slice(slice::from_raw_parts(address_ptr, 20));
 		let input: &[u8] = slice::from_raw_parts(input_ptr, input_len as usize);
 		let result: &mut[u8] = slice::from_raw_parts_mut(result_ptr, result_len as usize);
-		match r.borrow().call_code(gas, &address, input, result) {
+		match e.call_code(gas, &address, input, result) {
 			Ok(_r) => 0,
 			Err(_e) => 1
 		}
@@ -122,11 +254,11 @@ pub unsafe extern "C" fn scall(
 	result_len: u32,
 ) -> i32
 {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let address = Address::from_slice(slice::from_raw_parts(address_ptr, 20));
 		let input: &[u8] = slice::from_raw_parts(input_ptr, input_len as usize);
 		let result: &mut[u8] = slice::from_raw_parts_mut(result_ptr, result_len as usize);
-		match r.borrow().call_code(gas, &address, input, result) {
+		match e.static_call(gas, &address, input, result) {
 			Ok(_r) => 0,
 			Err(_e) => 1
 		}
@@ -136,18 +268,18 @@ pub unsafe extern "C" fn scall(
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn suicide(refund_ptr: *const u8) {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let address = Address::from_slice(slice::from_raw_parts(refund_ptr, 20));
-		r.borrow().suicide(&address)
+		e.suicide(&address)
 	})
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn blockhash(number: i64, dest: *mut u8) -> i32 {
-	EXTERNAL.with(|r| {
-		match r.borrow().blockhash(number as u64) {
-			Ok(result) => { ptr::copy(result.as_ptr(), dest, Address::len_bytes()); 0 },
+	with_external(|e| {
+		match e.blockhash(number as u64) {
+			Ok(result) => { ptr::copy(result.as_ptr(), dest, H256::len_bytes()); 0 },
 			Err(_e) => 1
 		}
 	})
@@ -156,24 +288,24 @@ pub unsafe extern "C" fn blockhash(number: i64, dest: *mut u8) -> i32 {
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn coinbase(dest: *mut u8) {
-	EXTERNAL.with(|r| {
-		ptr::copy(r.borrow().coinbase().as_mut_ptr(), dest, 20);
+	with_external(|e| {
+		ptr::copy(e.coinbase().as_mut_ptr(), dest, 20);
 	})
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn timestamp() -> i64 {
-	EXTERNAL.with(|r| {
-		r.borrow().timestamp() as i64
+	with_external(|e| {
+		e.timestamp() as i64
 	})
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn blocknumber() -> i64 {
-	EXTERNAL.with(|r| {
-		r.borrow().blocknumber() as i64
+	with_external(|e| {
+		e.blocknumber() as i64
 	})
 }
 
@@ -181,8 +313,8 @@ pub unsafe extern "C" fn blocknumber() -> i64 {
 #[no_mangle]
 pub unsafe extern "C" fn difficulty(dest: *mut u8) {
 	let mut dest = slice::from_raw_parts_mut(dest, 32);
-	EXTERNAL.with(|r| {
-		r.borrow().difficulty().to_big_endian(&mut dest);
+	with_external(|e| {
+		e.difficulty().to_big_endian(&mut dest);
 	});
 }
 
@@ -190,70 +322,70 @@ pub unsafe extern "C" fn difficulty(dest: *mut u8) {
 #[no_mangle]
 pub unsafe extern "C" fn gaslimit(dest: *mut u8) {
 	let mut dest = slice::from_raw_parts_mut(dest, 32);
-	EXTERNAL.with(|r| {
-		r.borrow().gas_limit().to_big_endian(&mut dest);
+	with_external(|e| {
+		e.gas_limit().to_big_endian(&mut dest);
 	});
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn sender(dest: *mut u8) {
-	EXTERNAL.with(|r| {
-		ptr::copy(r.borrow().sender().as_ptr(), dest , 20);
+	with_external(|e| {
+		ptr::copy(e.sender().as_ptr(), dest , 20);
 	});
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn address(dest: *mut u8) {
-	EXTERNAL.with(|r| {
-		ptr::copy(r.borrow().address().as_ptr(), dest , 20);
+	with_external(|e| {
+		ptr::copy(e.address().as_ptr(), dest , 20);
 	});
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn value(dest: *mut u8) {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let mut dest = slice::from_raw_parts_mut(dest, 32);
-		r.borrow().value().to_big_endian(&mut dest);
+		e.value().to_big_endian(&mut dest);
 	})
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn origin(dest: *mut u8) {
-	EXTERNAL.with(|r| {
-		ptr::copy(r.borrow().origin().as_ptr(), dest , 20);
+	with_external(|e| {
+		ptr::copy(e.origin().as_ptr(), dest , 20);
 	});
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn balance(address_ptr: *const u8, balance_ptr: *mut u8) {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let address = Address::from_slice(slice::from_raw_parts(address_ptr, 20));
 		let mut balance =  slice::from_raw_parts_mut(balance_ptr, 32);
-		r.borrow().balance(&address).to_big_endian(&mut balance);
+		e.balance(&address).to_big_endian(&mut balance);
 	});
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn debug(str_ptr: *const u8, str_len: u32) {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let msg = String::from_raw_parts(str_ptr as *mut _, str_len as usize, str_len as usize);
-		r.borrow().debug_log(msg);
+		e.debug_log(msg);
 	});
 }
 
 #[doc(hidden)]
 #[no_mangle]
 pub unsafe extern "C" fn elog(topic_ptr: *const u8, topic_count: u32, data_ptr: *const u8, data_len: u32) {
-	EXTERNAL.with(|r| {
+	with_external(|e| {
 		let topics: &[H256] = slice::from_raw_parts(topic_ptr as *const H256, topic_count as usize);
 		let data: &[u8] = slice::from_raw_parts(data_ptr, data_len as usize);
-		r.borrow().elog(topics, data);
+		e.elog(topics, data);
 	});
 }
 
@@ -0,0 +1,218 @@
+//! Fault injection into the mocked externs
+#[cfg(feature = "std")]
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
+
+use pwasm_std::types::{H256, Address};
+
+use hex;
+use prelude::*;
+
+/// Fault injected by `ExternalInstance`, configured with `ExternalBuilder`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+	/// `nth` (starting from 1) `pwasm_ethereum::call` to `address` fails
+	CallFails { address: Address, nth: usize },
+	/// `pwasm_ethereum::create` fails
+	CreateFails,
+	/// `pwasm_ethereum::block_hash` returns an error
+	BlockhashFails,
+	/// Execution runs out of gas on the `at`th (starting from 1) extern invocation
+	OutOfGas { at: usize },
+	/// `pwasm_ethereum::read` of `key` returns `value` instead of the stored word
+	CorruptStorageRead { key: H256, value: [u8; 32] },
+}
+
+impl fmt::Display for Fault {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Fault::CallFails { ref address, nth } => write!(f, "call #{} to {} fails", nth, hex::to_hex(address)),
+			Fault::CreateFails => write!(f, "create fails"),
+			Fault::BlockhashFails => write!(f, "blockhash fails"),
+			Fault::OutOfGas { at } => write!(f, "out of gas at extern #{}", at),
+			Fault::CorruptStorageRead { ref key, ref value } => write!(f, "read of {} returns {}", hex::to_hex(key), hex::to_hex(value)),
+		}
+	}
+}
+
+/// Panic payload of an injected out of gas, caught by the frame which ran out of gas
+#[cfg(feature = "std")]
+struct OutOfGas;
+
+#[cfg(feature = "std")]
+thread_local!(
+	/// Number of frames running inside `catch_out_of_gas` on this thread
+	static CATCHING: Cell<usize> = Cell::new(0)
+);
+
+#[derive(Default, Debug)]
+struct State {
+	externs: usize,
+	calls: BTreeMap<Address, usize>,
+	hits: BTreeMap<usize, usize>,
+}
+
+/// Faults to inject with counters of the invocations they depend on
+///
+/// Clones share counters and hits, so the report covers all instances derived with `ext_update` or `ext_transact`.
+#[derive(Clone, Default, Debug)]
+pub struct Faults {
+	faults: Vec<Fault>,
+	state: Rc<RefCell<State>>,
+}
+
+impl Faults {
+	/// Adds a fault to inject
+	pub fn push(&mut self, fault: Fault) {
+		self.faults.push(fault);
+	}
+
+	/// Returns which faults were injected so far
+	pub fn report(&self) -> FaultReport {
+		let state = self.state.borrow();
+		let mut report = FaultReport::default();
+		for (index, fault) in self.faults.iter().enumerate() {
+			match state.hits.get(&index) {
+				Some(hits) => report.hit.push((fault.clone(), *hits)),
+				None => report.missed.push(fault.clone()),
+			}
+		}
+		report
+	}
+
+	/// Returns the first configured fault matching `matches`, recording the hit
+	fn inject<F: Fn(&Fault) -> bool>(&self, matches: F) -> Option<&Fault> {
+		let index = self.faults.iter().position(matches)?;
+		*self.state.borrow_mut().hits.entry(index).or_insert(0) += 1;
+		Some(&self.faults[index])
+	}
+
+	/// Counts an extern invocation, running out of gas if the count matches an `OutOfGas` fault
+	pub(crate) fn extern_invoked(&self) {
+		let count = {
+			let mut state = self.state.borrow_mut();
+			state.externs += 1;
+			state.externs
+		};
+		if self.inject(|fault| *fault == Fault::OutOfGas { at: count }).is_some() {
+			out_of_gas(count);
+		}
+	}
+
+	/// Counts a call to `address`, returning `true` if it should fail
+	pub(crate) fn call_fails(&self, address: &Address) -> bool {
+		let nth = {
+			let mut state = self.state.borrow_mut();
+			let calls = state.calls.entry(*address).or_insert(0);
+			*calls += 1;
+			*calls
+		};
+		self.inject(|fault| *fault == Fault::CallFails { address: *address, nth: nth }).is_some()
+	}
+
+	pub(crate) fn create_fails(&self) -> bool {
+		self.inject(|fault| *fault == Fault::CreateFails).is_some()
+	}
+
+	pub(crate) fn blockhash_fails(&self) -> bool {
+		self.inject(|fault| *fault == Fault::BlockhashFails).is_some()
+	}
+
+	/// Returns the corrupted value of `key` if its read should be corrupted
+	pub(crate) fn corrupt_read(&self, key: &H256) -> Option<[u8; 32]> {
+		match self.inject(|fault| match *fault {
+			Fault::CorruptStorageRead { key: ref corrupted, .. } => corrupted == key,
+			_ => false,
+		}) {
+			Some(&Fault::CorruptStorageRead { value, .. }) => Some(value),
+			_ => None,
+		}
+	}
+}
+
+/// Unwinds to the frame which ran out of gas, or fails the test with a message outside of any frame
+#[cfg(feature = "std")]
+fn out_of_gas(at: usize) -> ! {
+	if CATCHING.with(|catching| catching.get()) > 0 {
+		// `resume_unwind` doesn't run the panic hook, so a caught out of gas prints nothing
+		panic::resume_unwind(Box::new(OutOfGas))
+	}
+	panic!("out of gas injected at extern #{}", at)
+}
+
+#[cfg(not(feature = "std"))]
+fn out_of_gas(at: usize) -> ! {
+	panic!("out of gas injected at extern #{}", at)
+}
+
+/// Faults which were and weren't injected during a test
+#[derive(Clone, Default, Debug)]
+pub struct FaultReport {
+	/// Injected faults with the number of times they were hit
+	pub hit: Vec<(Fault, usize)>,
+	/// Configured faults which were never hit
+	pub missed: Vec<Fault>,
+}
+
+impl FaultReport {
+	/// Panics if some configured fault was never hit
+	pub fn assert_all_hit(&self) {
+		if !self.missed.is_empty() {
+			panic!("Some injected faults were never hit:\n{}", self);
+		}
+	}
+}
+
+impl fmt::Display for FaultReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for &(ref fault, hits) in &self.hit {
+			writeln!(f, "  hit {} time(s): {}", hits, fault)?;
+		}
+		for fault in &self.missed {
+			writeln!(f, "  not hit: {}", fault)?;
+		}
+		Ok(())
+	}
+}
+
+/// Runs `f`, returning `None` if it ran out of gas injected with `Fault::OutOfGas`
+#[cfg(feature = "std")]
+pub(crate) fn catch_out_of_gas<T, F: FnOnce() -> T>(f: F) -> Option<T> {
+	let _catching = Catching::enter();
+	match panic::catch_unwind(AssertUnwindSafe(f)) {
+		Ok(result) => Some(result),
+		Err(ref payload) if payload.is::<OutOfGas>() => None,
+		Err(payload) => panic::resume_unwind(payload),
+	}
+}
+
+/// Runs `f`, without unwinding an injected out of gas aborts the whole test
+#[cfg(not(feature = "std"))]
+pub(crate) fn catch_out_of_gas<T, F: FnOnce() -> T>(f: F) -> Option<T> {
+	Some(f())
+}
+
+/// Counts a running `catch_out_of_gas` until dropped
+#[cfg(feature = "std")]
+struct Catching;
+
+#[cfg(feature = "std")]
+impl Catching {
+	fn enter() -> Catching {
+		CATCHING.with(|catching| catching.set(catching.get() + 1));
+		Catching
+	}
+}
+
+#[cfg(feature = "std")]
+impl Drop for Catching {
+	fn drop(&mut self) {
+		// Thread-locals may already be destroyed if a frame is dropped while the thread exits
+		let _ = CATCHING.try_with(|catching| catching.set(catching.get() - 1));
+	}
+}
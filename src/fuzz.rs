@@ -0,0 +1,188 @@
+//! Entry helper for coverage-guided fuzzing with `cargo fuzz`
+use std::{cmp, fmt, panic};
+
+use pwasm_std::types::{U256, Address};
+
+use builder::ExternalBuilder;
+use external::{Endpoint, Receipt};
+use hex;
+use {ext_reset, ext_transact};
+
+/// Maximum number of bytes of a transaction value
+const VALUE_BYTES: usize = 32;
+
+/// Transaction decoded from fuzzer input
+#[derive(Clone, Debug)]
+pub struct FuzzTransaction {
+	pub from: Address,
+	pub value: U256,
+	pub input: Vec<u8>,
+}
+
+/// Panic of the contract while executing fuzzer input
+#[derive(Clone, Debug)]
+pub struct Crash {
+	/// Index of the transaction which panicked
+	pub step: usize,
+	/// Panic message
+	pub message: String,
+	/// Transactions up to and including the panicking one
+	pub transactions: Vec<FuzzTransaction>,
+}
+
+impl fmt::Display for Crash {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Contract panicked at transaction #{}: {}", self.step, self.message)?;
+		for (index, tx) in self.transactions.iter().enumerate() {
+			write!(f, "\n  #{} from {} value {} input {}",
+				index, hex::to_hex(&tx.from), hex::u256_to_hex(&tx.value), hex::to_hex(&tx.input))?;
+		}
+		Ok(())
+	}
+}
+
+/// Turns raw fuzzer bytes into transactions against a contract, starting from a fresh world every run
+///
+/// Every transaction is decoded from the input as:
+///
+/// * 1 byte: index of the sender
+/// * 1 byte: length of the value (modulo 33), followed by the big-endian value
+/// * 1 byte: index of the selector, only if selectors were added
+/// * 2 bytes: big-endian length of the call data, followed by the call data
+///
+/// Truncated input is padded with zeros.
+///
+/// # Example
+/// ```no_run
+/// # extern crate pwasm_test;
+/// # use pwasm_test::{FuzzTarget, Endpoint};
+/// # fn main () {
+/// #
+///	// fuzz_targets/token.rs: fuzz_target!(|data: &[u8]| target().fuzz(data));
+///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///	let owner = "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap();
+///	let target = FuzzTarget::new(contract, || Endpoint::ok())
+///		.setup(move |e| e.balance_of(owner, 1000.into()))
+///		.sender(owner)
+///		.selector([0xa9, 0x05, 0x9c, 0xbb]);
+///	target.fuzz(&[0, 1, 10, 0, 0, 64]);
+/// # }
+/// ```
+pub struct FuzzTarget {
+	address: Address,
+	endpoint: Box<Fn() -> Endpoint>,
+	setup: Box<Fn(ExternalBuilder) -> ExternalBuilder>,
+	senders: Vec<Address>,
+	selectors: Vec<[u8; 4]>,
+}
+
+impl FuzzTarget {
+	/// Creates a target for the contract at `address`,
+	/// `endpoint` is called once per run to create a fresh contract
+	pub fn new<N>(address: Address, endpoint: N) -> FuzzTarget
+		where N: Fn() -> Endpoint + 'static
+	{
+		FuzzTarget {
+			address: address,
+			endpoint: Box::new(endpoint),
+			setup: Box::new(|e: ExternalBuilder| e),
+			senders: Vec::new(),
+			selectors: Vec::new(),
+		}
+	}
+
+	/// Sets up the world every run starts from
+	pub fn setup<F>(mut self, setup: F) -> FuzzTarget
+		where F: Fn(ExternalBuilder) -> ExternalBuilder + 'static
+	{
+		self.setup = Box::new(setup);
+		self
+	}
+
+	/// Adds an account transactions are sent from, zero address is used if none is added
+	pub fn sender(mut self, sender: Address) -> FuzzTarget {
+		self.senders.push(sender);
+		self
+	}
+
+	/// Adds a known selector, call data is prefixed with one of the added selectors
+	pub fn selector(mut self, selector: [u8; 4]) -> FuzzTarget {
+		self.selectors.push(selector);
+		self
+	}
+
+	/// Decodes fuzzer input into transactions
+	pub fn transactions(&self, mut data: &[u8]) -> Vec<FuzzTransaction> {
+		let mut transactions = Vec::new();
+		while !data.is_empty() {
+			let from = match self.senders.len() {
+				0 => Address::zero(),
+				len => self.senders[take_byte(&mut data) as usize % len],
+			};
+			let value_len = take_byte(&mut data) as usize % (VALUE_BYTES + 1);
+			let value = U256::from_big_endian(take(&mut data, value_len));
+			let mut input = match self.selectors.len() {
+				0 => Vec::new(),
+				len => self.selectors[take_byte(&mut data) as usize % len].to_vec(),
+			};
+			let input_len = (take_byte(&mut data) as usize) << 8 | take_byte(&mut data) as usize;
+			input.extend_from_slice(take(&mut data, input_len));
+			transactions.push(FuzzTransaction {
+				from: from,
+				value: value,
+				input: input,
+			});
+		}
+		transactions
+	}
+
+	/// Resets the world and executes transactions decoded from `data`,
+	/// returns the crash if the contract panics
+	pub fn run(&self, data: &[u8]) -> Result<Vec<Receipt>, Crash> {
+		ext_reset(|e| (self.setup)(e).endpoint(self.address, (self.endpoint)()));
+		let transactions = self.transactions(data);
+		let mut receipts = Vec::with_capacity(transactions.len());
+		for (step, tx) in transactions.iter().enumerate() {
+			let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+				ext_transact(tx.from, self.address, tx.value, &tx.input)
+			}));
+			match result {
+				Ok(receipt) => receipts.push(receipt),
+				Err(payload) => return Err(Crash {
+					step: step,
+					message: panic_message(&payload),
+					transactions: transactions[..step + 1].to_vec(),
+				}),
+			}
+		}
+		Ok(receipts)
+	}
+
+	/// Entry point for `fuzz_target!`, panics with the executed transactions if the contract panics
+	pub fn fuzz(&self, data: &[u8]) {
+		if let Err(crash) = self.run(data) {
+			panic!("{}", crash);
+		}
+	}
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> &'a [u8] {
+	let len = cmp::min(len, data.len());
+	let (head, tail) = data.split_at(len);
+	*data = tail;
+	head
+}
+
+fn take_byte(data: &mut &[u8]) -> u8 {
+	take(data, 1).first().cloned().unwrap_or(0)
+}
+
+fn panic_message(payload: &Box<::std::any::Any + Send>) -> String {
+	if let Some(message) = payload.downcast_ref::<&str>() {
+		message.to_string()
+	} else if let Some(message) = payload.downcast_ref::<String>() {
+		message.clone()
+	} else {
+		"Box<Any>".to_owned()
+	}
+}
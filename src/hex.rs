@@ -0,0 +1,94 @@
+//! Hex and quantity encoding helpers
+use std::fmt::Write;
+
+use pwasm_std::types::U256;
+
+use prelude::*;
+
+/// Encodes bytes as `0x`-prefixed lowercase hex
+pub fn to_hex(bytes: &[u8]) -> String {
+	let mut result = String::with_capacity(2 + bytes.len() * 2);
+	result.push_str("0x");
+	for byte in bytes {
+		write!(result, "{:02x}", byte).expect("Writing to a String can't fail");
+	}
+	result
+}
+
+/// Decodes hex with an optional `0x` prefix, odd number of digits is left padded with zero
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+	let digits = strip_prefix(s);
+	let mut result = Vec::with_capacity(digits.len() / 2 + 1);
+	let mut chars = digits.chars();
+	if digits.len() % 2 == 1 {
+		result.push(chars.next()?.to_digit(16)? as u8);
+	}
+	while let Some(high) = chars.next() {
+		let low = chars.next()?;
+		result.push((high.to_digit(16)? * 16 + low.to_digit(16)?) as u8);
+	}
+	Some(result)
+}
+
+/// Decodes hex to a 32-byte word, shorter values are left padded with zeroes
+pub fn to_word(s: &str) -> Option<[u8; 32]> {
+	let bytes = from_hex(s)?;
+	if bytes.len() > 32 {
+		return None;
+	}
+	let mut word = [0u8; 32];
+	word[32 - bytes.len()..].copy_from_slice(&bytes);
+	Some(word)
+}
+
+/// Encodes `U256` as a hex quantity without leading zeroes, e.g. `0x2710`
+pub fn u256_to_hex(value: &U256) -> String {
+	let mut bytes = [0u8; 32];
+	value.to_big_endian(&mut bytes);
+	let hex = to_hex(&bytes);
+	let digits = hex[2..].trim_start_matches('0');
+	if digits.is_empty() {
+		"0x0".to_owned()
+	} else {
+		format!("0x{}", digits)
+	}
+}
+
+/// Parses `U256` either from a `0x`-prefixed hex or from a decimal string
+pub fn parse_u256(s: &str) -> Option<U256> {
+	if s.starts_with("0x") || s.starts_with("0X") {
+		let word = to_word(s)?;
+		return Some(U256::from_big_endian(&word));
+	}
+	if s.is_empty() {
+		return None;
+	}
+	let mut result = U256::zero();
+	for c in s.chars() {
+		let (shifted, mul_overflow) = result.overflowing_mul(U256::from(10));
+		let (added, add_overflow) = shifted.overflowing_add(U256::from(c.to_digit(10)?));
+		if mul_overflow || add_overflow {
+			return None;
+		}
+		result = added;
+	}
+	Some(result)
+}
+
+/// Parses `u64` either from a `0x`-prefixed hex or from a decimal string
+pub fn parse_u64(s: &str) -> Option<u64> {
+	let value = parse_u256(s)?;
+	if value > U256::from(u64::max_value()) {
+		None
+	} else {
+		Some(value.low_u64())
+	}
+}
+
+fn strip_prefix(s: &str) -> &str {
+	if s.starts_with("0x") || s.starts_with("0X") {
+		&s[2..]
+	} else {
+		s
+	}
+}
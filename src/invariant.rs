@@ -0,0 +1,222 @@
+//! Stateful invariant testing over random transaction sequences
+use std::fmt;
+
+use proptest::prelude::*;
+use proptest::collection;
+use proptest::strategy::Union;
+use proptest::test_runner::{Config, TestRunner, TestError, TestCaseError};
+use pwasm_std::types::Address;
+
+use builder::ExternalBuilder;
+use external::{Endpoint, ExternalInstance};
+use strategies::{abi_input, AbiArgs};
+use hex;
+use {ext_reset, ext_transact, ext_get};
+
+/// Default number of random sequences
+const DEFAULT_RUNS: u32 = 256;
+/// Default maximum number of transactions in a sequence
+const DEFAULT_DEPTH: usize = 16;
+
+/// Transaction of an invariant run
+#[derive(Clone, Debug)]
+pub struct InvariantCall {
+	/// Name of the action
+	pub action: String,
+	pub from: Address,
+	pub input: Vec<u8>,
+}
+
+/// Minimal sequence of transactions which breaks an invariant
+#[derive(Clone, Debug)]
+pub struct InvariantFailure {
+	/// Name of the broken invariant
+	pub invariant: String,
+	/// Transactions to replay, the invariant is broken after the last one
+	pub calls: Vec<InvariantCall>,
+}
+
+impl fmt::Display for InvariantFailure {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Invariant `{}` broken after {} transaction(s), minimal reproduction:", self.invariant, self.calls.len())?;
+		for (index, call) in self.calls.iter().enumerate() {
+			write!(f, "\n  #{} {} from {} input {}", index, call.action, hex::to_hex(&call.from), hex::to_hex(&call.input))?;
+		}
+		Ok(())
+	}
+}
+
+struct Action {
+	name: String,
+	input: BoxedStrategy<Vec<u8>>,
+}
+
+/// Runs random sequences of actions against a contract, checking invariants after every transaction.
+/// Failing sequences are shrunk to a minimal reproduction.
+///
+/// # Example
+/// ```
+/// # #[macro_use] extern crate proptest;
+/// # extern crate pwasm_std;
+/// # extern crate pwasm_ethereum;
+/// # extern crate pwasm_test;
+/// # use proptest::prelude::Just;
+/// # use pwasm_std::types::H256;
+/// # use pwasm_test::{Invariants, Endpoint};
+/// # use pwasm_test::strategies::u256;
+/// # fn main () {
+/// #
+///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///	let owner = "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap();
+///	Invariants::new(contract, || Endpoint::returning(Box::new(|_val, _input| Ok(Vec::new()))))
+///		.sender(owner)
+///		.action("noop", [0x5d, 0xfc, 0x2e, 0x4a], Just(()))
+///		.action("store", [0x60, 0x57, 0x36, 0x1d], (u256(),))
+///		.invariant("storage is untouched", move |world| world.storage_at(&contract, &H256::zero()) == [0u8; 32])
+///		.check();
+/// # }
+/// ```
+pub struct Invariants {
+	address: Address,
+	endpoint: Box<Fn() -> Endpoint>,
+	setup: Box<Fn(ExternalBuilder) -> ExternalBuilder>,
+	actions: Vec<Action>,
+	senders: Vec<Address>,
+	invariants: Vec<(String, Box<Fn(&ExternalInstance) -> bool>)>,
+	runs: u32,
+	depth: usize,
+}
+
+impl Invariants {
+	/// Creates a run for the contract at `address`,
+	/// `endpoint` is called once per sequence to create a fresh contract
+	pub fn new<N>(address: Address, endpoint: N) -> Invariants
+		where N: Fn() -> Endpoint + 'static
+	{
+		Invariants {
+			address: address,
+			endpoint: Box::new(endpoint),
+			setup: Box::new(|e: ExternalBuilder| e),
+			actions: Vec::new(),
+			senders: Vec::new(),
+			invariants: Vec::new(),
+			runs: DEFAULT_RUNS,
+			depth: DEFAULT_DEPTH,
+		}
+	}
+
+	/// Sets up the world every sequence starts from
+	pub fn setup<F>(mut self, setup: F) -> Invariants
+		where F: Fn(ExternalBuilder) -> ExternalBuilder + 'static
+	{
+		self.setup = Box::new(setup);
+		self
+	}
+
+	/// Adds a callable method with `selector`, arguments are generated by `args`
+	pub fn action<S>(mut self, name: &str, selector: [u8; 4], args: S) -> Invariants
+		where S: Strategy + 'static, S::Value: AbiArgs
+	{
+		self.actions.push(Action {
+			name: name.to_owned(),
+			input: abi_input(selector, args),
+		});
+		self
+	}
+
+	/// Adds an account transactions are sent from, zero address is used if none is added
+	pub fn sender(mut self, sender: Address) -> Invariants {
+		self.senders.push(sender);
+		self
+	}
+
+	/// Adds an invariant which must hold initially and after every transaction
+	pub fn invariant<F>(mut self, name: &str, invariant: F) -> Invariants
+		where F: Fn(&ExternalInstance) -> bool + 'static
+	{
+		self.invariants.push((name.to_owned(), Box::new(invariant)));
+		self
+	}
+
+	/// Sets the number of random sequences
+	pub fn runs(mut self, runs: u32) -> Invariants {
+		self.runs = runs;
+		self
+	}
+
+	/// Sets the maximum number of transactions in a sequence
+	pub fn depth(mut self, depth: usize) -> Invariants {
+		self.depth = depth;
+		self
+	}
+
+	/// Runs random sequences, returns the minimal failing sequence if an invariant is broken
+	///
+	/// # Panics
+	///
+	/// Panics if no action was added.
+	pub fn run(&self) -> Result<(), InvariantFailure> {
+		let mut runner = TestRunner::new(Config { cases: self.runs, ..Config::default() });
+		let result = runner.run(&self.sequence(), |calls| {
+			self.execute(&calls).map_err(|failure| TestCaseError::fail(failure.to_string()))
+		});
+		match result {
+			Ok(()) => Ok(()),
+			// Replay the shrunk sequence to find the transaction which breaks an invariant,
+			// a sequence which doesn't fail again is still reported with the reason proptest gave
+			Err(TestError::Fail(reason, calls)) => match self.execute(&calls) {
+				Err(failure) => Err(failure),
+				Ok(()) => Err(InvariantFailure {
+					invariant: reason.message().to_owned(),
+					calls: calls,
+				}),
+			},
+			Err(err) => panic!("{}", err),
+		}
+	}
+
+	/// Runs random sequences, panics with the minimal reproduction if an invariant is broken
+	pub fn check(&self) {
+		if let Err(failure) = self.run() {
+			panic!("{}", failure);
+		}
+	}
+
+	fn sequence(&self) -> BoxedStrategy<Vec<InvariantCall>> {
+		assert!(!self.actions.is_empty(), "At least one action is required for invariant testing");
+		let senders = if self.senders.is_empty() { vec![Address::zero()] } else { self.senders.clone() };
+		let actions = Union::new(self.actions.iter().map(|action| {
+			let name = action.name.clone();
+			action.input.clone().prop_map(move |input| (name.clone(), input)).boxed()
+		}));
+		let call = (0..senders.len(), actions).prop_map(move |(sender, (action, input))| InvariantCall {
+			action: action,
+			from: senders[sender],
+			input: input,
+		});
+		collection::vec(call, 1..self.depth + 1).boxed()
+	}
+
+	fn execute(&self, calls: &[InvariantCall]) -> Result<(), InvariantFailure> {
+		ext_reset(|e| (self.setup)(e).endpoint(self.address, (self.endpoint)()));
+		self.check_invariants(&[])?;
+		for (index, call) in calls.iter().enumerate() {
+			ext_transact(call.from, self.address, 0.into(), &call.input);
+			self.check_invariants(&calls[..index + 1])?;
+		}
+		Ok(())
+	}
+
+	fn check_invariants(&self, calls: &[InvariantCall]) -> Result<(), InvariantFailure> {
+		let world = ext_get();
+		for &(ref name, ref invariant) in &self.invariants {
+			if !(**invariant)(&world) {
+				return Err(InvariantFailure {
+					invariant: name.clone(),
+					calls: calls.to_vec(),
+				});
+			}
+		}
+		Ok(())
+	}
+}
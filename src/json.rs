@@ -0,0 +1,300 @@
+//! JSON world state fixtures in genesis `alloc` style
+//!
+//! ```json
+//! {
+//!   "number": "0x10",
+//!   "timestamp": "0x5be9b3c0",
+//!   "difficulty": "0x20000",
+//!   "gasLimit": "0x7a1200",
+//!   "coinbase": "0xc257274276a4e539741ca11b590b9447b26a8051",
+//!   "alloc": {
+//!     "0x35da6abcb08f2b6164fe380bb6c47bd8f2304d55": {
+//!       "balance": "0x2710",
+//!       "storage": {
+//!         "0x0000000000000000000000000000000000000000000000000000000000000002": "0x0000000000000000000000000000000000000000000000000000000000002710"
+//!       }
+//!     }
+//!   }
+//! }
+//! ```
+use std::fmt;
+use std::error;
+use std::io::{self, Read};
+use std::fs::File;
+use std::path::Path;
+
+use serde_json::{self, Value, Map};
+use pwasm_std::types::{H256, U256, Address};
+
+use builder::ExternalBuilder;
+use external::ExternalInstance;
+use hex;
+
+/// Sections of a state dump which may contain accounts:
+/// chain spec `accounts`, `parity export state` dump `state` and genesis `alloc`
+const ACCOUNT_SECTIONS: &[&str] = &["accounts", "state", "alloc"];
+
+/// Error while loading a JSON state fixture
+#[derive(Debug)]
+pub enum FixtureError {
+	/// Fixture isn't a valid JSON
+	Json(serde_json::Error),
+	/// Fixture is a valid JSON but doesn't describe a state
+	Invalid(String),
+	/// Fixture file can't be read
+	Io(io::Error),
+}
+
+impl From<serde_json::Error> for FixtureError {
+	fn from(err: serde_json::Error) -> FixtureError {
+		FixtureError::Json(err)
+	}
+}
+
+impl From<io::Error> for FixtureError {
+	fn from(err: io::Error) -> FixtureError {
+		FixtureError::Io(err)
+	}
+}
+
+impl fmt::Display for FixtureError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			FixtureError::Json(ref err) => write!(f, "Invalid JSON: {}", err),
+			FixtureError::Invalid(ref msg) => write!(f, "Invalid state fixture: {}", msg),
+			FixtureError::Io(ref err) => write!(f, "Can't read state fixture: {}", err),
+		}
+	}
+}
+
+impl error::Error for FixtureError {
+	fn description(&self) -> &str {
+		"invalid state fixture"
+	}
+}
+
+impl ExternalBuilder {
+	/// Restores ExternalBuilder from a JSON state fixture, see `ExternalInstance::to_json`
+	///
+	/// # Example
+	/// ```
+	/// # extern crate pwasm_test;
+	/// # extern crate pwasm_ethereum;
+	/// # extern crate pwasm_std;
+	/// # use pwasm_std::types::H256;
+	/// # use pwasm_test::{ExternalBuilder, set_external};
+	/// # fn main () {
+	/// #
+	///	let builder = ExternalBuilder::from_json(r#"{
+	///		"number": "0x10",
+	///		"alloc": {
+	///			"0x35da6abcb08f2b6164fe380bb6c47bd8f2304d55": {
+	///				"balance": "10000",
+	///				"storage": { "0x00": "0x01" }
+	///			}
+	///		}
+	///	}"#).unwrap();
+	///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	set_external(Box::new(builder.address(contract).build()));
+	///	assert_eq!(pwasm_ethereum::block_number(), 16);
+	///	assert_eq!(pwasm_ethereum::balance(&contract), 10000.into());
+	///	assert_eq!(pwasm_ethereum::read(&H256::zero())[31], 1);
+	/// # }
+	/// ```
+	pub fn from_json(json: &str) -> Result<ExternalBuilder, FixtureError> {
+		let state: Value = serde_json::from_str(json)?;
+		let state = as_object(&state, "state")?;
+		let mut builder = ExternalBuilder::new();
+		if let Some(number) = state.get("number") {
+			builder = builder.blocknumber(parse_u64(number, "number")?);
+		}
+		if let Some(timestamp) = state.get("timestamp") {
+			builder = builder.timestamp(parse_u64(timestamp, "timestamp")?);
+		}
+		if let Some(difficulty) = state.get("difficulty") {
+			builder = builder.difficulty(parse_u256(difficulty, "difficulty")?);
+		}
+		if let Some(gas_limit) = state.get("gasLimit") {
+			builder = builder.gas_limit(parse_u256(gas_limit, "gasLimit")?);
+		}
+		if let Some(coinbase) = state.get("coinbase") {
+			builder = builder.coinbase(parse_address(as_str(coinbase, "coinbase")?)?);
+		}
+		if let Some(alloc) = state.get("alloc") {
+			for (address, account) in as_object(alloc, "alloc")? {
+				builder = builder.account_from_json(parse_address(address)?, account)?;
+			}
+		}
+		Ok(builder)
+	}
+
+	/// Imports balance, nonce, code and storage of a single account from a chain spec or a state dump file
+	///
+	/// Accounts are looked up in the chain spec `accounts` section, in the `state` section
+	/// of `parity export state --format json` output and in the genesis `alloc` section.
+	///
+	/// # Example
+	/// ```no_run
+	/// # extern crate pwasm_test;
+	/// # use pwasm_test::ext_reset;
+	/// # fn main () {
+	/// #
+	///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	///	ext_reset(|e| e
+	///		.import_account_state("fixtures/state.json", contract).unwrap()
+	///		.address(contract)
+	///	);
+	/// # }
+	/// ```
+	pub fn import_account_state<P: AsRef<Path>>(self, path: P, address: Address) -> Result<ExternalBuilder, FixtureError> {
+		let mut json = String::new();
+		File::open(path.as_ref())?.read_to_string(&mut json)?;
+		let dump: Value = serde_json::from_str(&json)?;
+		let dump = as_object(&dump, "state dump")?;
+		for section in ACCOUNT_SECTIONS {
+			if let Some(accounts) = dump.get(*section) {
+				for (key, account) in as_object(accounts, section)? {
+					if parse_address(key).ok() == Some(address) {
+						return self.account_from_json(address, account);
+					}
+				}
+			}
+		}
+		invalid(format!("account {} not found in {}", hex::to_hex(&address), path.as_ref().display()))
+	}
+
+	/// Loads balance, nonce, code and storage of a single account
+	pub(crate) fn account_from_json(mut self, address: Address, account: &Value) -> Result<ExternalBuilder, FixtureError> {
+		let account = as_object(account, "account")?;
+		if let Some(balance) = account.get("balance") {
+			self = self.balance_of(address, parse_u256(balance, "balance")?);
+		}
+		if let Some(nonce) = account.get("nonce") {
+			self = self.nonce(address, parse_u256(nonce, "nonce")?);
+		}
+		if let Some(code) = account.get("code") {
+			let code = parse_bytes(as_str(code, "code")?)?;
+			if !code.is_empty() {
+				self = self.code(address, code);
+			}
+		}
+		if let Some(storage) = account.get("storage") {
+			for (key, value) in as_object(storage, "storage")? {
+				let key = parse_word(key)?;
+				let value = parse_word(as_str(value, "storage value")?)?;
+				self = self.storage_at(address, H256::from_slice(&key), value);
+			}
+		}
+		Ok(self)
+	}
+}
+
+impl ExternalInstance {
+	/// Dumps accounts, balances, nonces, code, storage and block context as a JSON state fixture
+	/// which can be loaded back with `ExternalBuilder::from_json`
+	pub fn to_json(&self) -> String {
+		let mut alloc = Map::new();
+		for (address, balance) in self.balances.borrow().iter() {
+			account_mut(&mut alloc, address).insert("balance".to_owned(), Value::String(hex::u256_to_hex(balance)));
+		}
+		for (address, nonce) in self.nonces.borrow().iter() {
+			account_mut(&mut alloc, address).insert("nonce".to_owned(), Value::String(hex::u256_to_hex(nonce)));
+		}
+		for (address, code) in self.code.borrow().iter().filter(|&(_, code)| !code.is_empty()) {
+			account_mut(&mut alloc, address).insert("code".to_owned(), Value::String(hex::to_hex(code)));
+		}
+		for (address, slots) in self.storage.borrow().iter().filter(|&(_, slots)| !slots.is_empty()) {
+			let mut storage = Map::new();
+			for (key, value) in slots {
+				storage.insert(hex::to_hex(key), Value::String(hex::to_hex(value)));
+			}
+			account_mut(&mut alloc, address).insert("storage".to_owned(), Value::Object(storage));
+		}
+		for (_, account) in alloc.iter_mut() {
+			if let Value::Object(ref mut account) = *account {
+				if !account.contains_key("balance") {
+					account.insert("balance".to_owned(), Value::String("0x0".to_owned()));
+				}
+			}
+		}
+
+		let mut state = Map::new();
+		state.insert("number".to_owned(), Value::String(format!("{:#x}", self.blocknumber)));
+		state.insert("timestamp".to_owned(), Value::String(format!("{:#x}", self.timestamp)));
+		state.insert("difficulty".to_owned(), Value::String(hex::u256_to_hex(&self.difficulty)));
+		state.insert("gasLimit".to_owned(), Value::String(hex::u256_to_hex(&self.gas_limit)));
+		state.insert("coinbase".to_owned(), Value::String(hex::to_hex(&self.coinbase)));
+		state.insert("alloc".to_owned(), Value::Object(alloc));
+		serde_json::to_string_pretty(&Value::Object(state)).expect("Serializing a JSON value can't fail")
+	}
+}
+
+fn account_mut<'a>(alloc: &'a mut Map<String, Value>, address: &Address) -> &'a mut Map<String, Value> {
+	let key = hex::to_hex(address);
+	if !alloc.contains_key(&key) {
+		alloc.insert(key.clone(), Value::Object(Map::new()));
+	}
+	match alloc.get_mut(&key) {
+		Some(&mut Value::Object(ref mut account)) => account,
+		_ => unreachable!("Account entry was inserted as an object above; qed"),
+	}
+}
+
+fn invalid<T>(msg: String) -> Result<T, FixtureError> {
+	Err(FixtureError::Invalid(msg))
+}
+
+fn as_object<'a>(value: &'a Value, what: &str) -> Result<&'a Map<String, Value>, FixtureError> {
+	match value.as_object() {
+		Some(object) => Ok(object),
+		None => invalid(format!("{} must be an object, got {}", what, value)),
+	}
+}
+
+fn as_str<'a>(value: &'a Value, what: &str) -> Result<&'a str, FixtureError> {
+	match value.as_str() {
+		Some(s) => Ok(s),
+		None => invalid(format!("{} must be a string, got {}", what, value)),
+	}
+}
+
+fn parse_u256(value: &Value, what: &str) -> Result<U256, FixtureError> {
+	if let Some(number) = value.as_u64() {
+		return Ok(U256::from(number));
+	}
+	match hex::parse_u256(as_str(value, what)?) {
+		Some(number) => Ok(number),
+		None => invalid(format!("{} must be a hex or decimal number, got {}", what, value)),
+	}
+}
+
+fn parse_u64(value: &Value, what: &str) -> Result<u64, FixtureError> {
+	if let Some(number) = value.as_u64() {
+		return Ok(number);
+	}
+	match hex::parse_u64(as_str(value, what)?) {
+		Some(number) => Ok(number),
+		None => invalid(format!("{} must be a 64-bit hex or decimal number, got {}", what, value)),
+	}
+}
+
+fn parse_address(s: &str) -> Result<Address, FixtureError> {
+	match hex::from_hex(s) {
+		Some(ref bytes) if bytes.len() == 20 => Ok(Address::from_slice(bytes)),
+		_ => invalid(format!("{} is not an address", s)),
+	}
+}
+
+fn parse_bytes(s: &str) -> Result<Vec<u8>, FixtureError> {
+	match hex::from_hex(s) {
+		Some(bytes) => Ok(bytes),
+		None => invalid(format!("{} is not a hex string", s)),
+	}
+}
+
+fn parse_word(s: &str) -> Result<[u8; 32], FixtureError> {
+	match hex::to_word(s) {
+		Some(word) => Ok(word),
+		None => invalid(format!("{} is not a 32-byte hex word", s)),
+	}
+}
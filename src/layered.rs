@@ -0,0 +1,378 @@
+//! Interceptor chains around an `External`
+use std::any::Any;
+use std::rc::Rc;
+
+use pwasm_std::types::{H256, U256, Address};
+
+use external::{External, Error};
+use hex;
+use prelude::*;
+
+/// Interceptor of `External` methods
+///
+/// Every method receives the rest of the chain as `next` and passes the invocation through by default,
+/// so a layer only overrides the methods it's interested in.
+pub trait Layer {
+	fn balance(&self, next: &External, address: &Address) -> U256 {
+		next.balance(address)
+	}
+
+	fn storage_read(&self, next: &External, key: &H256) -> [u8; 32] {
+		next.storage_read(key)
+	}
+
+	fn storage_write(&self, next: &External, key: &H256, value: &[u8; 32]) {
+		next.storage_write(key, value)
+	}
+
+	fn suicide(&self, next: &External, refund: &Address) {
+		next.suicide(refund)
+	}
+
+	fn create(&self, next: &External, endowment: U256, code: &[u8]) -> Result<Address, Error> {
+		next.create(endowment, code)
+	}
+
+	fn call(&self, next: &External, gas: u64, address: &Address, val: U256, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		next.call(gas, address, val, input, result)
+	}
+
+	fn call_code(&self, next: &External, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		next.call_code(gas, address, input, result)
+	}
+
+	fn static_call(&self, next: &External, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		next.static_call(gas, address, input, result)
+	}
+
+	fn elog(&self, next: &External, topics: &[H256], data: &[u8]) {
+		next.elog(topics, data)
+	}
+
+	fn debug_log(&self, next: &External, msg: String) {
+		next.debug_log(msg)
+	}
+
+	fn blockhash(&self, next: &External, number: u64) -> Result<H256, Error> {
+		next.blockhash(number)
+	}
+
+	fn coinbase(&self, next: &External) -> Address {
+		next.coinbase()
+	}
+
+	fn timestamp(&self, next: &External) -> u64 {
+		next.timestamp()
+	}
+
+	fn blocknumber(&self, next: &External) -> u64 {
+		next.blocknumber()
+	}
+
+	fn difficulty(&self, next: &External) -> U256 {
+		next.difficulty()
+	}
+
+	fn gas_limit(&self, next: &External) -> U256 {
+		next.gas_limit()
+	}
+
+	fn sender(&self, next: &External) -> Address {
+		next.sender()
+	}
+
+	fn origin(&self, next: &External) -> Address {
+		next.origin()
+	}
+
+	fn value(&self, next: &External) -> U256 {
+		next.value()
+	}
+
+	fn address(&self, next: &External) -> Address {
+		next.address()
+	}
+}
+
+/// `External` which passes every invocation through a chain of layers before it reaches the inner one
+///
+/// Layers see invocations in the order they were added. Clones share the layers.
+///
+/// The `ext_*` helpers, such as `ext_get`, `ext_update`, `ext_transact`, `ext_expect_call` or `ext_dump_storage`,
+/// work on an installed `ExternalInstance` and panic with `WrongType` while a `Layered` one is installed.
+/// Its state is reached through `get_external::<Layered<ExternalInstance>>().inner()` instead.
+///
+/// ```
+/// # extern crate pwasm_test;
+/// # extern crate pwasm_ethereum;
+/// # use pwasm_test::{set_external, External, ExternalBuilder, Layer, Layered};
+/// # fn main () {
+/// #
+///	struct Frozen;
+///
+///	impl Layer for Frozen {
+///		fn timestamp(&self, _next: &External) -> u64 {
+///			1000
+///		}
+///	}
+///
+///	set_external(Box::new(Layered::new(ExternalBuilder::new().timestamp(5).blocknumber(7).build()).layer(Frozen)));
+///	assert_eq!(pwasm_ethereum::timestamp(), 1000);
+///	assert_eq!(pwasm_ethereum::block_number(), 7);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Layered<E> {
+	inner: E,
+	layers: Vec<Rc<Layer>>,
+}
+
+impl<E: External> Layered<E> {
+	/// Wraps `inner` without any layers
+	pub fn new(inner: E) -> Layered<E> {
+		Layered {
+			inner: inner,
+			layers: Vec::new(),
+		}
+	}
+
+	/// Adds `layer` below the already added ones
+	pub fn layer<L: Layer + 'static>(mut self, layer: L) -> Layered<E> {
+		self.layers.push(Rc::new(layer));
+		self
+	}
+
+	/// Returns wrapped `External`
+	pub fn inner(&self) -> &E {
+		&self.inner
+	}
+
+	fn chain(&self) -> Chain {
+		Chain {
+			layers: &self.layers,
+			inner: &self.inner,
+		}
+	}
+}
+
+/// Remaining part of a chain, passed to layers as `next`
+struct Chain<'a> {
+	layers: &'a [Rc<Layer>],
+	inner: &'a External,
+}
+
+impl<'a> Chain<'a> {
+	fn split(&self) -> Option<(&'a Layer, Chain<'a>)> {
+		self.layers.split_first().map(|(layer, rest)| {
+			let next = Chain {
+				layers: rest,
+				inner: self.inner,
+			};
+			(&**layer, next)
+		})
+	}
+}
+
+macro_rules! dispatch {
+	($chain: expr, $method: ident($($arg: expr),*)) => {
+		match $chain.split() {
+			Some((layer, next)) => layer.$method(&next, $($arg),*),
+			None => $chain.inner.$method($($arg),*),
+		}
+	}
+}
+
+impl<'a> External for Chain<'a> {
+	fn balance(&self, address: &Address) -> U256 {
+		dispatch!(self, balance(address))
+	}
+
+	fn storage_read(&self, key: &H256) -> [u8; 32] {
+		dispatch!(self, storage_read(key))
+	}
+
+	fn storage_write(&self, key: &H256, value: &[u8; 32]) {
+		dispatch!(self, storage_write(key, value))
+	}
+
+	fn suicide(&self, refund: &Address) {
+		dispatch!(self, suicide(refund))
+	}
+
+	fn create(&self, endowment: U256, code: &[u8]) -> Result<Address, Error> {
+		dispatch!(self, create(endowment, code))
+	}
+
+	fn call(&self, gas: u64, address: &Address, val: U256, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		dispatch!(self, call(gas, address, val, input, result))
+	}
+
+	fn call_code(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		dispatch!(self, call_code(gas, address, input, result))
+	}
+
+	fn static_call(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		dispatch!(self, static_call(gas, address, input, result))
+	}
+
+	fn elog(&self, topics: &[H256], data: &[u8]) {
+		dispatch!(self, elog(topics, data))
+	}
+
+	fn debug_log(&self, msg: String) {
+		dispatch!(self, debug_log(msg))
+	}
+
+	fn blockhash(&self, number: u64) -> Result<H256, Error> {
+		dispatch!(self, blockhash(number))
+	}
+
+	fn coinbase(&self) -> Address {
+		dispatch!(self, coinbase())
+	}
+
+	fn timestamp(&self) -> u64 {
+		dispatch!(self, timestamp())
+	}
+
+	fn blocknumber(&self) -> u64 {
+		dispatch!(self, blocknumber())
+	}
+
+	fn difficulty(&self) -> U256 {
+		dispatch!(self, difficulty())
+	}
+
+	fn gas_limit(&self) -> U256 {
+		dispatch!(self, gas_limit())
+	}
+
+	fn sender(&self) -> Address {
+		dispatch!(self, sender())
+	}
+
+	fn origin(&self) -> Address {
+		dispatch!(self, origin())
+	}
+
+	fn value(&self) -> U256 {
+		dispatch!(self, value())
+	}
+
+	fn address(&self) -> Address {
+		dispatch!(self, address())
+	}
+
+	fn as_any(&self) -> &Any {
+		self.inner.as_any()
+	}
+}
+
+impl<E: External + 'static> External for Layered<E> {
+	fn balance(&self, address: &Address) -> U256 {
+		self.chain().balance(address)
+	}
+
+	fn storage_read(&self, key: &H256) -> [u8; 32] {
+		self.chain().storage_read(key)
+	}
+
+	fn storage_write(&self, key: &H256, value: &[u8; 32]) {
+		self.chain().storage_write(key, value)
+	}
+
+	fn suicide(&self, refund: &Address) {
+		self.chain().suicide(refund)
+	}
+
+	fn create(&self, endowment: U256, code: &[u8]) -> Result<Address, Error> {
+		self.chain().create(endowment, code)
+	}
+
+	fn call(&self, gas: u64, address: &Address, val: U256, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		self.chain().call(gas, address, val, input, result)
+	}
+
+	fn call_code(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		self.chain().call_code(gas, address, input, result)
+	}
+
+	fn static_call(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		self.chain().static_call(gas, address, input, result)
+	}
+
+	fn elog(&self, topics: &[H256], data: &[u8]) {
+		self.chain().elog(topics, data)
+	}
+
+	fn debug_log(&self, msg: String) {
+		self.chain().debug_log(msg)
+	}
+
+	fn blockhash(&self, number: u64) -> Result<H256, Error> {
+		self.chain().blockhash(number)
+	}
+
+	fn coinbase(&self) -> Address {
+		self.chain().coinbase()
+	}
+
+	fn timestamp(&self) -> u64 {
+		self.chain().timestamp()
+	}
+
+	fn blocknumber(&self) -> u64 {
+		self.chain().blocknumber()
+	}
+
+	fn difficulty(&self) -> U256 {
+		self.chain().difficulty()
+	}
+
+	fn gas_limit(&self) -> U256 {
+		self.chain().gas_limit()
+	}
+
+	fn sender(&self) -> Address {
+		self.chain().sender()
+	}
+
+	fn origin(&self) -> Address {
+		self.chain().origin()
+	}
+
+	fn value(&self) -> U256 {
+		self.chain().value()
+	}
+
+	fn address(&self) -> Address {
+		self.chain().address()
+	}
+
+	fn as_any(&self) -> &Any {
+		self
+	}
+}
+
+/// Layer which panics on any storage write, e.g. to check that a constant method doesn't modify state
+///
+/// ```should_panic
+/// # extern crate pwasm_test;
+/// # extern crate pwasm_ethereum;
+/// # extern crate pwasm_std;
+/// # use pwasm_std::types::H256;
+/// # use pwasm_test::{set_external, ExternalBuilder, Layered, ReadOnly};
+/// # fn main () {
+/// #
+///	set_external(Box::new(Layered::new(ExternalBuilder::new().build()).layer(ReadOnly)));
+///	pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+/// # }
+/// ```
+pub struct ReadOnly;
+
+impl Layer for ReadOnly {
+	fn storage_write(&self, next: &External, key: &H256, value: &[u8; 32]) {
+		panic!("Storage write of {} to key {} of {} while storage is read-only",
+			hex::to_hex(value), hex::to_hex(key), hex::to_hex(&next.address()));
+	}
+}
@@ -0,0 +1,438 @@
+//! Declared storage layouts and decoded storage dumps
+//!
+//! A layout names the slots of a contract the way Solidity assigns them: every variable,
+//! mapping and dynamic array takes one slot, struct fields take consecutive slots.
+//! Mapping entries can't be enumerated from hashed keys, so they are found by trying
+//! every address the world knows about, every stored word and keys added with `Layout::key`.
+//!
+//! Every variable is decoded from a whole word: variables Solidity packs into one slot,
+//! e.g. an `address` followed by a `bool`, aren't supported. `ext_assert_storage_layout` fails
+//! on words which have more bytes set than their declared type takes, as such variables are likely packed.
+//! Structs stored in mappings and arrays are declared with `Kind`.
+//!
+//! ```
+//! # extern crate pwasm_test;
+//! # extern crate pwasm_std;
+//! # use pwasm_std::types::U256;
+//! # use pwasm_test::{ext_reset, ext_dump_storage};
+//! # use pwasm_test::accounts::alice;
+//! # use pwasm_test::layout::Layout;
+//! # use pwasm_test::slots;
+//! # fn main () {
+//! #
+//!	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+//!	let layout = Layout::new()
+//!		.value("totalSupply", 0, "uint256")
+//!		.mapping("balances", 1, "address", "uint256");
+//!	ext_reset(|e| e
+//!		.address(token)
+//!		.layout(token, layout.clone())
+//!		.storage_value(slots::slot(0), U256::from(10000))
+//!		.storage_value(slots::mapping(&slots::slot(1), &alice()), U256::from(9000))
+//!	);
+//!	assert_eq!(ext_dump_storage(&token), "totalSupply = 10000\nbalances[alice] = 9000");
+//! # }
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "std")]
+use std::thread;
+
+use pwasm_std::types::{H256, U256, Address};
+
+use abi;
+use accounts;
+use external::ExternalInstance;
+use externs::get_external;
+#[cfg(feature = "std")]
+use externs::try_get_external;
+use hex;
+use slots::{self, Word};
+use prelude::*;
+
+/// Number of dynamic array elements shown before the rest is skipped
+const MAX_ELEMENTS: u64 = 256;
+
+/// Type of a declared variable, used to declare structs stored in mappings and arrays
+///
+/// Constructors panic on types which can't be stored this way, like declaration methods of `Layout`.
+///
+/// ```
+/// # extern crate pwasm_test;
+/// # use pwasm_test::layout::{Kind, Layout};
+/// # fn main () {
+/// #
+///	let order = Kind::structure(&[("maker", Kind::of("address")), ("amount", Kind::of("uint256"))]);
+///	let layout = Layout::new()
+///		.variable("orders", 0, Kind::mapping("uint256", order.clone()))
+///		.variable("queue", 1, Kind::array(order));
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Kind(Repr);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Repr {
+	/// Value type taking a single word, e.g. `uint256`
+	Word(String),
+	/// Mapping with a key type and a value kind
+	Mapping(String, Box<Kind>),
+	/// Dynamic array of elements of some kind
+	Array(Box<Kind>),
+	/// Named fields in consecutive slots
+	Struct(Vec<(String, Kind)>),
+}
+
+impl Kind {
+	/// Kind of `uint256`, `address[]` or `mapping(address => uint256)`
+	pub fn of(ty: &str) -> Kind {
+		Kind::parse(ty).unwrap_or_else(|err| panic!("{}", err))
+	}
+
+	/// Kind of a mapping from `key`, which is a word type, to values of `value` kind
+	pub fn mapping(key: &str, value: Kind) -> Kind {
+		Kind(Repr::Mapping(word_type(key).unwrap_or_else(|err| panic!("{}", err)), Box::new(value)))
+	}
+
+	/// Kind of a dynamic array of `element` kind
+	pub fn array(element: Kind) -> Kind {
+		Kind(Repr::Array(Box::new(element)))
+	}
+
+	/// Kind of a struct with `(name, kind)` fields, each one taking its own slots
+	pub fn structure(fields: &[(&str, Kind)]) -> Kind {
+		Kind(Repr::Struct(fields.iter().map(|&(name, ref kind)| (name.to_owned(), kind.clone())).collect()))
+	}
+
+	/// Parses `uint256`, `address[]` or `mapping(address => uint256)`
+	fn parse(ty: &str) -> Result<Kind, String> {
+		let ty: String = ty.chars().filter(|c| !c.is_whitespace()).collect();
+		if ty.starts_with("mapping(") && ty.ends_with(')') {
+			let inner = &ty["mapping(".len()..ty.len() - 1];
+			let arrow = inner.find("=>").ok_or_else(|| format!("{} is not a mapping, e.g. mapping(address=>uint256)", ty))?;
+			let key = word_type(&inner[..arrow])?;
+			return Ok(Kind(Repr::Mapping(key, Box::new(Kind::parse(&inner[arrow + 2..])?))));
+		}
+		if ty.ends_with("[]") {
+			return Ok(Kind(Repr::Array(Box::new(Kind::parse(&ty[..ty.len() - 2])?))));
+		}
+		word_type(&ty).map(|ty| Kind(Repr::Word(ty)))
+	}
+
+	/// Number of slots taken
+	fn words(&self) -> u64 {
+		match self.0 {
+			Repr::Struct(ref fields) => fields.iter().map(|&(_, ref kind)| kind.words()).sum(),
+			_ => 1,
+		}
+	}
+}
+
+/// Checks `ty` is a type stored in a single word
+fn word_type(ty: &str) -> Result<String, String> {
+	let size = |prefix: &str, max: usize| ty.starts_with(prefix) && match &ty[prefix.len()..] {
+		"" => prefix != "bytes",
+		digits => digits.parse::<usize>().map(|size| size > 0 && size <= max && (max == 32 || size % 8 == 0)).unwrap_or(false),
+	};
+	if ty == "address" || ty == "bool" || size("uint", 256) || size("int", 256) || size("bytes", 32) {
+		Ok(ty.to_owned())
+	} else {
+		Err(format!("{} is not a storage word type, e.g. uint256, address or bytes32", ty))
+	}
+}
+
+/// Returns `true` if `word` can be a value of type `ty`
+///
+/// Values of types shorter than a word leave the rest of it zero, or sign-extended for `int`.
+fn fits(ty: &str, word: &[u8; 32]) -> bool {
+	let bytes = |prefix: &str| ty[prefix.len()..].parse::<usize>().unwrap_or(256) / 8;
+	match ty {
+		"address" => word[..12].iter().all(|byte| *byte == 0),
+		"bool" => word[..31].iter().all(|byte| *byte == 0) && word[31] <= 1,
+		ty if ty.starts_with("uint") => word[..32 - bytes("uint")].iter().all(|byte| *byte == 0),
+		ty if ty.starts_with("int") => {
+			let sign = if word[32 - bytes("int")] & 0x80 == 0 { 0 } else { 0xff };
+			word[..32 - bytes("int")].iter().all(|byte| *byte == sign)
+		},
+		ty if ty.starts_with("bytes") => word[ty[5..].parse::<usize>().unwrap_or(32)..].iter().all(|byte| *byte == 0),
+		_ => true,
+	}
+}
+
+/// Formats `word` as a value of type `ty`, as hex if it doesn't decode
+fn format_word(ty: &str, word: &[u8; 32]) -> String {
+	abi::decode_params(&[ty.to_owned()], word)
+		.ok()
+		.and_then(|mut values| values.pop())
+		.unwrap_or_else(|| hex::to_hex(word))
+}
+
+#[derive(Clone, Debug)]
+struct Variable {
+	name: String,
+	slot: H256,
+	kind: Kind,
+}
+
+/// Storage layout of a contract
+///
+/// Registered with `ExternalBuilder::layout`, used by `ext_dump_storage` and `ext_assert_storage_layout`.
+/// Declaration methods panic on types which can't be stored this way, such as `string`.
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+	variables: Vec<Variable>,
+	keys: Vec<[u8; 32]>,
+}
+
+impl Layout {
+	pub fn new() -> Layout {
+		Layout::default()
+	}
+
+	fn declare(mut self, name: &str, slot: u64, kind: Result<Kind, String>) -> Layout {
+		match kind {
+			Ok(kind) => self.variables.push(Variable { name: name.to_owned(), slot: slots::slot(slot), kind: kind }),
+			Err(err) => panic!("{}", err),
+		}
+		self
+	}
+
+	/// Declares a variable of type `ty` at `slot`, e.g. `uint256`, `address[]` or `mapping(address => uint256)`
+	pub fn value(self, name: &str, slot: u64, ty: &str) -> Layout {
+		self.declare(name, slot, Kind::parse(ty))
+	}
+
+	/// Declares a mapping from `key` to `value` at `slot`, `value` can be a mapping itself
+	pub fn mapping(self, name: &str, slot: u64, key: &str, value: &str) -> Layout {
+		let kind = word_type(key).and_then(|key| Ok(Kind(Repr::Mapping(key, Box::new(Kind::parse(value)?)))));
+		self.declare(name, slot, kind)
+	}
+
+	/// Declares a dynamic array of `element` at `slot`
+	pub fn array(self, name: &str, slot: u64, element: &str) -> Layout {
+		let kind = Kind::parse(element).map(Kind::array);
+		self.declare(name, slot, kind)
+	}
+
+	/// Declares a struct starting at `slot` with `(name, type)` fields, each one taking its own slots
+	///
+	/// The struct takes as many slots as its fields, so the next variable is declared after them.
+	pub fn structure(self, name: &str, slot: u64, fields: &[(&str, &str)]) -> Layout {
+		let kind = fields.iter()
+			.map(|&(field, ty)| Kind::parse(ty).map(|kind| (field.to_owned(), kind)))
+			.collect::<Result<Vec<_>, _>>()
+			.map(|fields| Kind(Repr::Struct(fields)));
+		self.declare(name, slot, kind)
+	}
+
+	/// Declares a variable of some `kind` at `slot`, e.g. a mapping to structs or an array of them
+	pub fn variable(self, name: &str, slot: u64, kind: Kind) -> Layout {
+		self.declare(name, slot, Ok(kind))
+	}
+
+	/// Adds a mapping key tried besides known addresses and stored words, e.g. a token id
+	pub fn key<K: Word>(mut self, key: K) -> Layout {
+		self.keys.push(key.to_word());
+		self
+	}
+
+	/// Decoded storage of the contract at `address`, one `name = value` line per variable
+	///
+	/// Variables are listed even when zero, mapping entries only when set.
+	pub fn dump(&self, instance: &ExternalInstance, address: &Address) -> String {
+		self.walk(instance, address).lines.join("\n")
+	}
+
+	/// Keys of storage words of the contract at `address` which aren't covered by the layout
+	pub fn undeclared(&self, instance: &ExternalInstance, address: &Address) -> Vec<H256> {
+		let walk = self.walk(instance, address);
+		walk.storage.keys().filter(|key| !walk.declared.contains(*key)).cloned().collect()
+	}
+
+	/// Variables of the contract at `address` whose words don't fit their declared type
+	///
+	/// Solidity packs variables shorter than a word, e.g. two `uint128`, into one slot,
+	/// such a word has more bytes set than the first variable takes.
+	pub fn possibly_packed(&self, instance: &ExternalInstance, address: &Address) -> Vec<String> {
+		self.walk(instance, address).misfits
+	}
+
+	fn walk(&self, instance: &ExternalInstance, address: &Address) -> Walk {
+		let storage = instance.storage.borrow().get(address).cloned().unwrap_or_default();
+		let mut walk = Walk {
+			candidates: candidates(instance, &storage, &self.keys),
+			storage: storage,
+			lines: Vec::new(),
+			declared: BTreeSet::new(),
+			misfits: Vec::new(),
+		};
+		for variable in &self.variables {
+			walk.visit(&variable.name, variable.slot, &variable.kind, true);
+		}
+		walk
+	}
+}
+
+/// Words tried as mapping keys: `keys`, addresses known to `instance` and values in `storage`
+fn candidates(instance: &ExternalInstance, storage: &BTreeMap<H256, [u8; 32]>, keys: &[[u8; 32]]) -> Vec<[u8; 32]> {
+	let mut addresses: BTreeSet<Address> = accounts::book().into_iter().collect();
+	addresses.extend(vec![instance.sender, instance.origin, instance.address, instance.coinbase]);
+	addresses.extend(instance.labels.keys().cloned());
+	addresses.extend(instance.endpoints.keys().cloned());
+	addresses.extend(instance.balances.borrow().keys().cloned());
+	addresses.extend(instance.nonces.borrow().keys().cloned());
+	addresses.extend(instance.code.borrow().keys().cloned());
+	addresses.extend(instance.storage.borrow().keys().cloned());
+
+	let mut candidates: BTreeSet<[u8; 32]> = keys.iter().cloned().collect();
+	candidates.extend(addresses.iter().map(|address| address.to_word()));
+	candidates.extend(storage.values().cloned());
+	candidates.into_iter().collect()
+}
+
+struct Walk {
+	candidates: Vec<[u8; 32]>,
+	storage: BTreeMap<H256, [u8; 32]>,
+	lines: Vec<String>,
+	declared: BTreeSet<H256>,
+	/// `path: type` of words which don't fit their type
+	misfits: Vec<String>,
+}
+
+impl Walk {
+	fn word(&self, slot: &H256) -> [u8; 32] {
+		self.storage.get(slot).cloned().unwrap_or([0u8; 32])
+	}
+
+	/// Records `path = value` lines of a `kind` stored at `slot`, zero words only if `all`
+	fn visit(&mut self, path: &str, slot: H256, kind: &Kind, all: bool) {
+		match kind.0 {
+			Repr::Word(ref ty) => {
+				self.declared.insert(slot);
+				let word = self.word(&slot);
+				if !fits(ty, &word) {
+					self.misfits.push(format!("{}: {}", path, ty));
+				}
+				if all || word != [0u8; 32] {
+					self.lines.push(format!("{} = {}", path, format_word(ty, &word)));
+				}
+			},
+			Repr::Mapping(ref key, ref value) => {
+				let candidates = self.candidates.clone();
+				for candidate in candidates.iter().filter(|candidate| fits(key, candidate)) {
+					let path = format!("{}[{}]", path, format_word(key, candidate));
+					self.visit(&path, slots::mapping(&slot, candidate), value, false);
+				}
+			},
+			Repr::Array(ref element) => {
+				self.declared.insert(slot);
+				let length = U256::from_word(&self.word(&slot));
+				if all || !length.is_zero() {
+					self.lines.push(format!("{}.length = {}", path, length));
+				}
+				let shown = if length > U256::from(MAX_ELEMENTS) { MAX_ELEMENTS } else { length.low_u64() };
+				for index in 0..shown {
+					let path = format!("{}[{}]", path, index);
+					self.visit(&path, slots::array_element(&slot, index, element.words()), element, true);
+				}
+			},
+			Repr::Struct(ref fields) => {
+				let mut offset = 0;
+				for &(ref name, ref field) in fields {
+					let path = format!("{}.{}", path, name);
+					self.visit(&path, slots::offset(&slot, offset), field, all);
+					offset += field.words();
+				}
+			},
+		}
+	}
+}
+
+fn layout_of(instance: &ExternalInstance, address: &Address) -> Layout {
+	match instance.layouts.get(address) {
+		Some(layout) => layout.clone(),
+		None => panic!("No storage layout is declared for {}, add one with ExternalBuilder::layout", abi::format_address(address)),
+	}
+}
+
+/// Decoded storage of the contract at `address` using the layout registered with `ExternalBuilder::layout`
+pub fn ext_dump_storage(address: &Address) -> String {
+	let instance = get_external::<ExternalInstance>();
+	layout_of(&instance, address).dump(&instance, address)
+}
+
+/// Panics if the contract at `address` has storage words outside of its registered layout
+/// or words which don't fit their declared type, see `Layout::possibly_packed`
+///
+/// Mapping entries whose keys aren't known addresses or stored words count as undeclared,
+/// add their keys with `Layout::key`.
+///
+/// # Example
+/// ```should_panic
+/// # extern crate pwasm_test;
+/// # extern crate pwasm_std;
+/// # extern crate pwasm_ethereum;
+/// # use pwasm_std::types::U256;
+/// # use pwasm_test::{ext_reset, ext_assert_storage_layout};
+/// # use pwasm_test::layout::Layout;
+/// # use pwasm_test::slots::{self, Word};
+/// # fn main () {
+/// #
+///	let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///	ext_reset(|e| e.address(token).layout(token, Layout::new().value("totalSupply", 0, "uint256")));
+///	pwasm_ethereum::write(&slots::slot(3), &U256::from(1).to_word());
+///	// Panics listing slot 3 after "Storage of 0x35da..4d55 has words outside of its layout:"
+///	ext_assert_storage_layout(&token);
+/// # }
+/// ```
+pub fn ext_assert_storage_layout(address: &Address) {
+	let instance = get_external::<ExternalInstance>();
+	let layout = layout_of(&instance, address);
+	let undeclared = layout.undeclared(&instance, address);
+	let packed = layout.possibly_packed(&instance, address);
+	if undeclared.is_empty() && packed.is_empty() {
+		return;
+	}
+	let mut message = String::new();
+	if !undeclared.is_empty() {
+		message.push_str(&format!("Storage of {} has words outside of its layout:\n", abi::format_address(address)));
+		for key in undeclared {
+			message.push_str(&format!("  {} = {}\n", hex::to_hex(&key), hex::to_hex(&instance.storage_at(address, &key))));
+		}
+	}
+	if !packed.is_empty() {
+		message.push_str(&format!("Storage of {} has words which don't fit their declared type, \
+			the variables are possibly packed into one slot:\n", abi::format_address(address)));
+		for variable in packed {
+			message.push_str(&format!("  {}\n", variable));
+		}
+	}
+	message.push_str(&format!("Declared storage:\n  {}", layout.dump(&instance, address).replace("\n", "\n  ")));
+	panic!("{}", message);
+}
+
+/// Prints decoded storage of every contract with a registered layout if the thread panics
+/// before the printer is dropped, see `#[pwasm_test(dump_storage)]`
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct StoragePrinter;
+
+#[cfg(feature = "std")]
+impl StoragePrinter {
+	pub fn new() -> StoragePrinter {
+		StoragePrinter
+	}
+}
+
+#[cfg(feature = "std")]
+impl Drop for StoragePrinter {
+	fn drop(&mut self) {
+		if !thread::panicking() {
+			return;
+		}
+		if let Ok(instance) = try_get_external::<ExternalInstance>() {
+			for (address, layout) in &instance.layouts {
+				eprintln!("Storage of {}:\n  {}", abi::format_address(address), layout.dump(&instance, address).replace("\n", "\n  "));
+			}
+		}
+	}
+}
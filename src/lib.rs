@@ -1,17 +1,113 @@
 //! `pwasm_ethereum` test lib
 #![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(feature = "std"), feature(alloc, slice_concat_ext))]
 
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
 extern crate pwasm_std;
 extern crate pwasm_ethereum;
 extern crate pwasm_abi;
+extern crate tiny_keccak;
+extern crate pwasm_test_macros;
+#[cfg(feature = "std")]
+extern crate serde_json;
+#[cfg(feature = "std")]
+extern crate libc;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "wasm")]
+extern crate wasmi;
+#[cfg(all(feature = "proptest", feature = "std"))]
+#[macro_use]
+extern crate proptest;
 
+/// `std` paths backed by `core` and `alloc`, so modules can `use std::..` in both builds
+#[cfg(not(feature = "std"))]
+mod std {
+	pub use core::{any, cell, cmp, fmt, mem, ptr, slice};
+	pub use alloc::{borrow, boxed, collections, rc, string, vec};
+}
+
+mod prelude;
 mod external;
 mod externs;
 mod builder;
+mod hex;
+mod trace;
+mod layered;
+mod fault;
+mod abi;
+mod expect;
+mod assertions;
+pub mod accounts;
+pub mod slots;
+pub mod layout;
+#[doc(hidden)]
+pub mod macro_support;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+mod coverage;
+#[cfg(feature = "std")]
+mod record;
+#[cfg(feature = "std")]
+mod parity;
+#[cfg(feature = "std")]
+mod fuzz;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+mod differential;
+#[cfg(all(feature = "proptest", feature = "std"))]
+pub mod strategies;
+#[cfg(all(feature = "proptest", feature = "std"))]
+mod invariant;
 
-pub use external::{Endpoint, External, ExternalInstance, Error};
+pub use external::{Endpoint, External, ExternalInstance, Error, Call, LogEntry, Receipt, contract_address};
 pub use builder::ExternalBuilder;
+pub use layered::{Layer, Layered, ReadOnly};
+pub use fault::{Fault, Faults, FaultReport};
+pub use abi::{Abi, AbiArgs, Signature, selector};
+pub use expect::ExpectedCall;
+pub use layout::{ext_dump_storage, ext_assert_storage_layout};
+pub use assertions::{assert_log_eq, assert_event_eq, assert_call_eq, assert_method_call_eq, assert_word_eq, ext_assert_storage, diff_bytes};
+pub use trace::{CallKind, StorageWrite, TraceFrame};
+#[cfg(feature = "std")]
+pub use trace::TracePrinter;
+#[cfg(feature = "std")]
+pub use layout::StoragePrinter;
+#[cfg(feature = "std")]
+pub use coverage::{AbiCoverage, ContractCoverage, MethodCoverage, abi_coverage, record_abi_coverage};
+pub use pwasm_test_macros::pwasm_test;
 pub use externs::*;
+#[cfg(feature = "std")]
+pub use json::FixtureError;
+#[cfg(feature = "std")]
+pub use record::{Interaction, Recorder, Replay};
+#[cfg(feature = "std")]
+pub use fuzz::{FuzzTarget, FuzzTransaction, Crash};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmContract, WasmError};
+#[cfg(feature = "wasm")]
+pub use differential::{Differential, Divergence, Transaction};
+#[cfg(all(feature = "proptest", feature = "std"))]
+pub use invariant::{Invariants, InvariantCall, InvariantFailure};
+
+use pwasm_std::types::{H256, U256, Address};
+
+use externs::with_external_instance;
+use prelude::*;
+use slots::Word;
+use trace::Context;
+
+/// Base gas cost of every transaction
+const TX_GAS: u64 = 21000;
+/// Gas paid for every zero byte of transaction input
+const TX_DATA_ZERO_GAS: u64 = 4;
+/// Gas paid for every non-zero byte of transaction input
+const TX_DATA_NON_ZERO_GAS: u64 = 68;
 
 ///	Allows to mock `pwasm_ethereum::*` calls
 ///
@@ -34,6 +130,33 @@ pub fn ext_reset<F>(updater: F) where F: Fn(ExternalBuilder) -> ExternalBuilder
 	set_external(Box::new(ext));
 }
 
+///	Installs mocks for `pwasm_ethereum::*` calls until the returned guard is dropped
+///
+///	Unlike `ext_reset`, the previously installed mocks are restored afterwards, so scopes can be nested.
+///
+///	# Example
+///
+/// ```
+/// extern crate pwasm_ethereum;
+/// extern crate pwasm_test;
+///
+///	use pwasm_test::{ext_scope, ExternalInstance};
+///
+/// fn main () {
+///		let _outer = ext_scope(|e| e.value(100.into()));
+///		{
+///			let inner = ext_scope(|e| e.value(5.into()));
+///			assert_eq!(pwasm_ethereum::value(), 5.into());
+///			assert!(inner.get::<ExternalInstance>().is_ok());
+///		}
+///		assert_eq!(pwasm_ethereum::value(), 100.into());
+/// }
+/// ```
+pub fn ext_scope<F>(updater: F) -> ExtGuard where F: Fn(ExternalBuilder) -> ExternalBuilder {
+	let ext = updater(ExternalBuilder::new()).build();
+	ExtGuard::install(Box::new(ext))
+}
+
 #[doc(inline)]
 ///	Updates `pwasm_ethereum::*` mocks
 ///
@@ -57,13 +180,303 @@ pub fn ext_update<F>(updater: F) where F: Fn(ExternalBuilder) -> ExternalBuilder
 	let old_ext = get_external::<ExternalInstance>();
 	let log = old_ext.log.clone();
 	let calls = old_ext.calls.clone();
+	let trace = old_ext.trace.clone();
 	let builder = ExternalBuilder::from(old_ext);
 	let mut ext = updater(builder).build();
 	ext.log = log;
 	ext.calls = calls;
+	ext.trace = trace;
 	set_external(Box::new(ext));
 }
 
+///	Executes a transaction against the mocked world
+///
+///	Invokes the endpoint registered at `to` with `sender` and `origin` being `from` and `address` being `to`,
+///	after transferring `value` from `from` to `to` and incrementing the nonce of `from`.
+///	Storage, balances, nonces, created contracts and logs are reverted if the endpoint fails.
+///	A transaction to an address without an endpoint is a plain value transfer.
+///	`sender`, `origin`, `address` and `value` of the world are back to their previous values afterwards.
+///	The receipt carries the trace of all nested calls. The trace is printed if the transaction panics,
+///	`#[pwasm_test(print_trace)]` also prints traces of finished transactions if the test fails later.
+///	Its `gas_used` is the intrinsic gas only, execution of endpoints isn't metered.
+///	A transaction whose value would overflow the recipient's balance fails without changing the state.
+///
+///	# Example
+///
+/// ```
+/// extern crate pwasm_ethereum;
+/// extern crate pwasm_test;
+///
+///	use pwasm_test::{ext_reset, ext_transact, Endpoint};
+///
+/// fn main () {
+///		let owner = "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap();
+///		let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///		ext_reset(|e| e
+///			.balance_of(owner, 1000.into())
+///			.endpoint(contract, Endpoint::returning(Box::new(|_val, _input| {
+///				Ok(pwasm_ethereum::sender().to_vec())
+///			})))
+///		);
+///		let receipt = ext_transact(owner, contract, 100.into(), &[]);
+///		assert!(receipt.status);
+///		assert_eq!(&receipt.output[..], &owner[..]);
+///		assert_eq!(pwasm_ethereum::balance(&contract), 100.into());
+/// }
+/// ```
+pub fn ext_transact(from: Address, to: Address, value: U256, input: &[u8]) -> Receipt {
+	let gas_used = intrinsic_gas(input);
+	#[cfg(feature = "std")]
+	let _printer = with_external_instance(|instance| trace::UnwindPrinter::new(&instance.trace.borrow()));
+	// The installed instance is used in place, copying it would copy every recorded trace
+	let started = with_external_instance(|instance| {
+		let from_balance = instance.balance_of(&from);
+		let to_balance = instance.balance_of(&to);
+		// Extreme generated balances could overflow the recipient's one, such a transaction is rejected like an unfunded one
+		if from_balance < value || (from != to && to_balance.checked_add(value).is_none()) {
+			return None;
+		}
+		// The sender's nonce is used up even if the transaction reverts
+		let nonce = instance.bump_nonce(&from);
+		let snapshot = instance.snapshot();
+		if from != to {
+			let mut balances = instance.balances.borrow_mut();
+			balances.insert(from, from_balance - value);
+			balances.insert(to, to_balance + value);
+		}
+		let context = Context {
+			sender: from,
+			address: to,
+			value: value,
+		};
+		let mut frame = TraceFrame::new(CallKind::Call, from, to, value, instance.gas_limit.low_u64(), input);
+		frame.transaction = true;
+		frame.block = instance.blocknumber;
+		frame.hash = transaction_hash(&from, &nonce, &to, &value, input);
+		instance.trace.borrow_mut().enter(context, frame);
+		let calls_before = instance.calls.borrow().len();
+		let logs_before = instance.log.borrow().len();
+		Some((snapshot, instance.endpoints.get(&to).cloned(), calls_before, logs_before))
+	});
+	let (snapshot, endpoint, calls_before, logs_before) = match started {
+		Some(started) => started,
+		None => return Receipt { gas_used: gas_used, ..Receipt::default() },
+	};
+
+	let (result, error) = match endpoint {
+		Some(endpoint) => match fault::catch_out_of_gas(|| endpoint.invoke(value, input)) {
+			Some(result) => (result, "reverted"),
+			None => (Err(Error), "out of gas"),
+		},
+		None => (Ok(Vec::new()), "reverted"),
+	};
+
+	// The endpoint may have replaced the installed instance, e.g. with `ext_update`
+	with_external_instance(|instance| {
+		match result {
+			Ok(ref output) => instance.trace.borrow_mut().exit(output, None),
+			Err(_) => instance.trace.borrow_mut().exit(&[], Some(error.to_owned())),
+		}
+		if result.is_err() {
+			instance.revert(snapshot);
+		}
+		let logs = instance.log.borrow()[logs_before..].to_vec();
+		let calls = instance.calls.borrow()[calls_before..].to_vec();
+		let trace = instance.trace.borrow().frames().last().cloned().unwrap_or_default();
+		Receipt {
+			block: instance.blocknumber,
+			status: result.is_ok(),
+			output: result.unwrap_or_default(),
+			logs: logs,
+			gas_used: gas_used,
+			calls: calls,
+			trace: trace,
+		}
+	})
+}
+
+/// Keccak of the fields of an unsigned transaction, see `TraceFrame::transaction_hash`
+fn transaction_hash(from: &Address, nonce: &U256, to: &Address, value: &U256, input: &[u8]) -> H256 {
+	let mut preimage = Vec::with_capacity(20 + 32 + 20 + 32 + input.len());
+	preimage.extend_from_slice(from);
+	preimage.extend_from_slice(&nonce.to_word());
+	preimage.extend_from_slice(to);
+	preimage.extend_from_slice(&value.to_word());
+	preimage.extend_from_slice(input);
+	H256::from_slice(&tiny_keccak::keccak256(&preimage))
+}
+
+fn intrinsic_gas(input: &[u8]) -> u64 {
+	input.iter().fold(TX_GAS, |gas, byte| {
+		gas + if *byte == 0 { TX_DATA_ZERO_GAS } else { TX_DATA_NON_ZERO_GAS }
+	})
+}
+
+///	Mines `blocks` blocks, moving `pwasm_ethereum::block_number()` and `pwasm_ethereum::timestamp()` forward
+///
+///	Timestamp is advanced by the block time set with `ExternalBuilder::block_time` for every block.
+///	Hashes of the mined blocks become available through `pwasm_ethereum::blockhash`.
+///
+///	# Example
+///
+/// ```
+/// extern crate pwasm_ethereum;
+/// extern crate pwasm_test;
+///
+///	use pwasm_test::{ext_reset, ext_mine};
+///
+/// fn main () {
+///		ext_reset(|e| e.blocknumber(10).timestamp(1000).block_time(15));
+///		ext_mine(2);
+///		assert_eq!(pwasm_ethereum::block_number(), 12);
+///		assert_eq!(pwasm_ethereum::timestamp(), 1030);
+///		assert!(pwasm_ethereum::block_hash(11).is_ok());
+/// }
+/// ```
+pub fn ext_mine(blocks: u64) {
+	let mut instance = get_external::<ExternalInstance>();
+	instance.mine(blocks);
+	set_external(Box::new(instance));
+}
+
+///	Moves `pwasm_ethereum::timestamp()` forward by `secs` seconds without mining a block
+///
+///	# Example
+///
+/// ```
+/// extern crate pwasm_ethereum;
+/// extern crate pwasm_test;
+///
+///	use pwasm_test::{ext_reset, ext_advance_time};
+///
+/// fn main () {
+///		ext_reset(|e| e.timestamp(1000));
+///		ext_advance_time(3600);
+///		assert_eq!(pwasm_ethereum::timestamp(), 4600);
+/// }
+/// ```
+pub fn ext_advance_time(secs: u64) {
+	let mut instance = get_external::<ExternalInstance>();
+	instance.advance_time(secs);
+	set_external(Box::new(instance));
+}
+
+///	Sets storage value for some `key` of the current contract in the installed world, encoded as a word
+///
+///	`ExternalInstance::set_storage_*` only change the instance they are called on,
+///	which for `ext_get()` is a copy of the installed one.
+///
+///	# Example
+///
+/// ```
+/// extern crate pwasm_ethereum;
+/// extern crate pwasm_std;
+/// extern crate pwasm_test;
+///
+///	use pwasm_std::types::U256;
+///	use pwasm_test::{ext_reset, ext_set_storage_value};
+///	use pwasm_test::slots::{self, Word};
+///
+/// fn main () {
+///		ext_reset(|e| e);
+///		ext_set_storage_value(&slots::slot(0), &U256::from(10000));
+///		assert_eq!(U256::from_word(&pwasm_ethereum::read(&slots::slot(0))), 10000.into());
+/// }
+/// ```
+pub fn ext_set_storage_value<T: Word>(key: &H256, value: &T) {
+	let instance = get_external::<ExternalInstance>();
+	instance.set_storage_value(key, value);
+	set_external(Box::new(instance));
+}
+
+///	Sets storage value for some `key` of the current contract in the installed world to a number
+pub fn ext_set_storage_u256(key: &H256, value: U256) {
+	ext_set_storage_value(key, &value)
+}
+
+///	Sets storage value for some `key` of the current contract in the installed world to a right-aligned address
+pub fn ext_set_storage_address(key: &H256, value: Address) {
+	ext_set_storage_value(key, &value)
+}
+
+///	Sets storage value for some `key` of the current contract in the installed world to `0` or `1`
+pub fn ext_set_storage_bool(key: &H256, value: bool) {
+	ext_set_storage_value(key, &value)
+}
+
+///	Expects the contract to call `address`, the expectation is checked when the returned value is dropped
+///
+///	Inputs of calls are decoded with the signature given to `with_selector`, so mismatch messages
+///	show arguments, e.g. `#0 transfer(0x16a0772b17ae004e6645e0e95bf50ad69498a34e, 50) value 0: args differ from byte 63`.
+///
+///	# Example
+///
+/// ```
+/// extern crate pwasm_ethereum;
+/// extern crate pwasm_std;
+/// extern crate pwasm_test;
+///
+///	use pwasm_std::types::{U256, Address};
+///	use pwasm_test::{ext_reset, ext_expect_call, selector, AbiArgs, Endpoint};
+///
+/// fn main () {
+///		let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///		let owner: Address = "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap();
+///		ext_reset(|e| e.endpoint(token, Endpoint::ok()));
+///		let _transfer = ext_expect_call(token)
+///			.with_selector("transfer(address,uint256)")
+///			.with_args((owner, U256::from(100)))
+///			.times(1);
+///
+///		let mut input = selector("transfer(address,uint256)").to_vec();
+///		input.extend((owner, U256::from(100)).encode());
+///		pwasm_ethereum::call(20000, &token, 0.into(), &input, &mut []).unwrap();
+/// }
+/// ```
+pub fn ext_expect_call(address: Address) -> ExpectedCall {
+	ExpectedCall::new(address)
+}
+
+///	Panics if the contract called an address without an endpoint with `pwasm_ethereum::call`, `call_code` or `static_call`
+///
+///	`#[pwasm_test(no_unexpected_calls)]` runs this check at the end of the test.
+///
+///	# Example
+///
+/// ```
+/// extern crate pwasm_ethereum;
+/// extern crate pwasm_test;
+///
+///	use pwasm_test::{ext_reset, ext_assert_no_unexpected_calls, Endpoint};
+///
+/// fn main () {
+///		let token = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///		ext_reset(|e| e.endpoint(token, Endpoint::ok()));
+///		pwasm_ethereum::call(2000, &token, 0.into(), &[], &mut []).unwrap();
+///		ext_assert_no_unexpected_calls();
+/// }
+/// ```
+pub fn ext_assert_no_unexpected_calls() {
+	let instance = ext_get();
+	let mut unexpected = Vec::new();
+	for frame in instance.traces() {
+		unexpected_calls(&instance, &frame, &mut unexpected);
+	}
+	if !unexpected.is_empty() {
+		panic!("Unexpected calls to addresses without an endpoint:\n  {}", unexpected.join("\n  "));
+	}
+}
+
+/// Collects calls of every kind made by contracts in `frame` to addresses without an endpoint
+fn unexpected_calls(instance: &ExternalInstance, frame: &TraceFrame, unexpected: &mut Vec<String>) {
+	if !frame.is_transaction() && frame.kind != CallKind::Create && !instance.endpoints.contains_key(&frame.to) {
+		unexpected.push(format!("{} {} with input {}", frame.kind, hex::to_hex(&frame.to), hex::to_hex(&frame.input)));
+	}
+	for call in &frame.calls {
+		unexpected_calls(instance, call, unexpected);
+	}
+}
+
 #[doc(hidden)]
 /// Returns current ExternalInstance
 pub fn ext_get() -> ExternalInstance {
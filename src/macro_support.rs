@@ -0,0 +1,22 @@
+//! Conversions used by the code `#[pwasm_test]` expands to, literals are validated by the attribute
+use pwasm_std::types::{H256, U256, Address};
+
+use hex;
+
+pub fn address(s: &str) -> Address {
+	Address::from_slice(&hex::from_hex(s).expect("Address is validated by #[pwasm_test]; qed"))
+}
+
+pub fn u256(s: &str) -> U256 {
+	hex::parse_u256(s).unwrap_or_else(|| panic!("{} doesn't fit into 256 bits", s))
+}
+
+pub fn word(s: &str) -> [u8; 32] {
+	let mut word = [0u8; 32];
+	u256(s).to_big_endian(&mut word);
+	word
+}
+
+pub fn h256(s: &str) -> H256 {
+	H256::from_slice(&word(s))
+}
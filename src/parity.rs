@@ -0,0 +1,103 @@
+//! Export of call traces in the JSON format of Parity's `trace_*` RPC methods
+use std::collections::BTreeMap;
+
+use serde_json::{self, Value, Map};
+use pwasm_std::types::H256;
+
+use external::ExternalInstance;
+use trace::{CallKind, TraceFrame};
+use hex;
+
+impl TraceFrame {
+	/// Serializes the frame and its nested frames as `trace_transaction` does
+	///
+	/// Frames are flattened in depth-first order, each one having `action`, `result` (or `error`),
+	/// `subtraces`, `traceAddress` and `type`. Frames of transactions also have `blockNumber`,
+	/// `blockHash` and `transactionHash`, see `TraceFrame::transaction_hash`. `blockHash` is null
+	/// as the frame doesn't know hashes of mined blocks, `ExternalInstance::traces_to_parity_json` fills it.
+	/// Unlike Parity, results have no `gasUsed` because execution of endpoints isn't metered.
+	pub fn to_parity_json(&self) -> String {
+		let mut traces = Vec::new();
+		flatten(self, &mut Vec::new(), &location(self, None), &mut traces);
+		to_string(traces)
+	}
+}
+
+impl ExternalInstance {
+	/// Serializes traces of all finished transactions as `trace_block` does,
+	/// `transactionPosition` is the index of the transaction since the world was set up
+	///
+	/// `blockHash` is null for transactions of the block which isn't mined yet.
+	pub fn traces_to_parity_json(&self) -> String {
+		let mut traces = Vec::new();
+		for (position, frame) in self.traces().iter().enumerate() {
+			let mut location = location(frame, Some(&self.blockhashes));
+			location.insert("transactionPosition".to_owned(), Value::from(position));
+			flatten(frame, &mut Vec::new(), &location, &mut traces);
+		}
+		to_string(traces)
+	}
+}
+
+/// Block and transaction fields shared by all frames of a transaction, empty for frames which aren't transactions
+fn location(frame: &TraceFrame, blockhashes: Option<&BTreeMap<u64, H256>>) -> Map<String, Value> {
+	let mut location = Map::new();
+	if frame.is_transaction() {
+		let block_hash = blockhashes
+			.and_then(|hashes| hashes.get(&frame.block()))
+			.map_or(Value::Null, |hash| Value::String(hex::to_hex(hash)));
+		location.insert("blockNumber".to_owned(), Value::from(frame.block()));
+		location.insert("blockHash".to_owned(), block_hash);
+		location.insert("transactionHash".to_owned(), Value::String(hex::to_hex(&frame.transaction_hash())));
+	}
+	location
+}
+
+fn flatten(frame: &TraceFrame, trace_address: &mut Vec<usize>, location: &Map<String, Value>, traces: &mut Vec<Value>) {
+	let mut trace = location.clone();
+	let mut action = Map::new();
+	action.insert("from".to_owned(), Value::String(hex::to_hex(&frame.from)));
+	action.insert("gas".to_owned(), Value::String(format!("{:#x}", frame.gas)));
+	action.insert("value".to_owned(), Value::String(hex::u256_to_hex(&frame.value)));
+	let mut result = Map::new();
+	if frame.kind == CallKind::Create {
+		action.insert("init".to_owned(), Value::String(hex::to_hex(&frame.input)));
+		result.insert("address".to_owned(), Value::String(hex::to_hex(&frame.to)));
+		result.insert("code".to_owned(), Value::String(hex::to_hex(&frame.output)));
+		trace.insert("type".to_owned(), Value::String("create".to_owned()));
+	} else {
+		action.insert("callType".to_owned(), Value::String(call_type(frame.kind).to_owned()));
+		action.insert("to".to_owned(), Value::String(hex::to_hex(&frame.to)));
+		action.insert("input".to_owned(), Value::String(hex::to_hex(&frame.input)));
+		result.insert("output".to_owned(), Value::String(hex::to_hex(&frame.output)));
+		trace.insert("type".to_owned(), Value::String("call".to_owned()));
+	}
+	trace.insert("action".to_owned(), Value::Object(action));
+	match frame.error {
+		None => { trace.insert("result".to_owned(), Value::Object(result)); },
+		// Parity omits `result` of failed frames
+		Some(ref error) => { trace.insert("error".to_owned(), Value::String(error.clone())); },
+	}
+	trace.insert("subtraces".to_owned(), Value::from(frame.calls.len()));
+	trace.insert("traceAddress".to_owned(), Value::Array(trace_address.iter().map(|index| Value::from(*index)).collect()));
+	traces.push(Value::Object(trace));
+
+	for (index, call) in frame.calls.iter().enumerate() {
+		trace_address.push(index);
+		flatten(call, trace_address, location, traces);
+		trace_address.pop();
+	}
+}
+
+fn call_type(kind: CallKind) -> &'static str {
+	match kind {
+		CallKind::Call => "call",
+		CallKind::Delegate => "delegatecall",
+		CallKind::Static => "staticcall",
+		CallKind::Create => "create",
+	}
+}
+
+fn to_string(traces: Vec<Value>) -> String {
+	serde_json::to_string_pretty(&Value::Array(traces)).expect("Serializing a JSON value can't fail")
+}
@@ -0,0 +1,7 @@
+//! Items of the `std` prelude which `core`'s prelude lacks, for modules built without `std`
+pub use std::borrow::ToOwned;
+pub use std::boxed::Box;
+pub use std::string::{String, ToString};
+pub use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+pub use alloc::slice::SliceConcatExt;
@@ -0,0 +1,475 @@
+//! Record-and-replay of extern interactions
+use std::cell::{Cell, RefCell};
+use std::any::Any;
+use std::cmp;
+use std::rc::Rc;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use serde_json::{self, Value, Map};
+use pwasm_std::types::{H256, U256, Address};
+
+use external::{External, Error};
+use json::FixtureError;
+use hex;
+
+/// Single extern invocation: its name, arguments and result
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interaction {
+	/// Call depth, interactions fired by endpoints during a `call` are recorded with a greater depth
+	pub depth: usize,
+	/// Name of the `External` method, e.g. `storage_read`
+	pub name: String,
+	/// Arguments, with bytes, hashes and addresses encoded as hex
+	pub args: Vec<Value>,
+	/// Result, `null` for methods which don't return anything
+	pub result: Value,
+}
+
+impl Interaction {
+	fn to_json(&self) -> Value {
+		let mut entry = Map::new();
+		entry.insert("depth".to_owned(), Value::from(self.depth as u64));
+		entry.insert("extern".to_owned(), Value::String(self.name.clone()));
+		entry.insert("args".to_owned(), Value::Array(self.args.clone()));
+		entry.insert("result".to_owned(), self.result.clone());
+		Value::Object(entry)
+	}
+
+	fn from_json(entry: &Value) -> Option<Interaction> {
+		Some(Interaction {
+			depth: entry.get("depth")?.as_u64()? as usize,
+			name: entry.get("extern")?.as_str()?.to_owned(),
+			args: entry.get("args")?.as_array()?.clone(),
+			result: entry.get("result")?.clone(),
+		})
+	}
+}
+
+/// `External` which passes everything to the inner one, recording every interaction
+///
+/// Clones share the recording, so a clone can be kept to save it after the test:
+///
+/// ```
+/// # extern crate pwasm_test;
+/// # extern crate pwasm_ethereum;
+/// # use pwasm_test::{set_external, ExternalBuilder, Recorder};
+/// # fn main () {
+/// #
+///	let recorder = Recorder::new(ExternalBuilder::new().timestamp(100).build());
+///	set_external(Box::new(recorder.clone()));
+///	assert_eq!(pwasm_ethereum::timestamp(), 100);
+///	assert_eq!(recorder.interactions()[0].name, "timestamp");
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Recorder<E> {
+	inner: E,
+	interactions: Rc<RefCell<Vec<Interaction>>>,
+	depth: Rc<Cell<usize>>,
+}
+
+impl<E: External> Recorder<E> {
+	/// Starts recording interactions with `inner`
+	pub fn new(inner: E) -> Recorder<E> {
+		Recorder {
+			inner: inner,
+			interactions: Rc::new(RefCell::new(Vec::new())),
+			depth: Rc::new(Cell::new(0)),
+		}
+	}
+
+	/// Returns wrapped `External`
+	pub fn inner(&self) -> &E {
+		&self.inner
+	}
+
+	/// Returns interactions recorded so far
+	pub fn interactions(&self) -> Vec<Interaction> {
+		self.interactions.borrow().clone()
+	}
+
+	/// Writes recorded interactions to a file, one JSON object per line
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		let mut file = File::create(path)?;
+		for interaction in self.interactions.borrow().iter() {
+			writeln!(file, "{}", interaction.to_json())?;
+		}
+		Ok(())
+	}
+
+	fn record(&self, name: &str, args: Vec<Value>, result: Value) {
+		self.interactions.borrow_mut().push(Interaction {
+			depth: self.depth.get(),
+			name: name.to_owned(),
+			args: args,
+			result: result,
+		});
+	}
+
+	/// Records an interaction which may fire nested ones, result is set by `finish`
+	///
+	/// Depth stays raised until the returned guard is dropped, also if a nested interaction panics.
+	fn begin(&self, name: &str, args: Vec<Value>) -> Nested {
+		self.record(name, args, Value::Null);
+		self.depth.set(self.depth.get() + 1);
+		Nested {
+			index: self.interactions.borrow().len() - 1,
+			depth: self.depth.clone(),
+		}
+	}
+
+	fn finish(&self, nested: Nested, result: Value) {
+		let index = nested.index;
+		drop(nested);
+		self.interactions.borrow_mut()[index].result = result;
+	}
+}
+
+/// Interaction recorded by `Recorder::begin` whose nested interactions are recorded with a greater depth
+struct Nested {
+	index: usize,
+	depth: Rc<Cell<usize>>,
+}
+
+impl Drop for Nested {
+	fn drop(&mut self) {
+		self.depth.set(self.depth.get() - 1);
+	}
+}
+
+impl<E: External + 'static> External for Recorder<E> {
+	fn balance(&self, address: &Address) -> U256 {
+		let result = self.inner.balance(address);
+		self.record("balance", vec![bytes(address)], u256(&result));
+		result
+	}
+
+	fn storage_read(&self, key: &H256) -> [u8; 32] {
+		let result = self.inner.storage_read(key);
+		self.record("storage_read", vec![bytes(key)], bytes(&result));
+		result
+	}
+
+	fn storage_write(&self, key: &H256, value: &[u8; 32]) {
+		self.inner.storage_write(key, value);
+		self.record("storage_write", vec![bytes(key), bytes(value)], Value::Null);
+	}
+
+	fn suicide(&self, refund: &Address) {
+		self.inner.suicide(refund);
+		self.record("suicide", vec![bytes(refund)], Value::Null);
+	}
+
+	fn create(&self, endowment: U256, code: &[u8]) -> Result<Address, Error> {
+		let nested = self.begin("create", vec![u256(&endowment), bytes(code)]);
+		let result = self.inner.create(endowment, code);
+		self.finish(nested, match result {
+			Ok(ref address) => bytes(address),
+			Err(_) => Value::Null,
+		});
+		result
+	}
+
+	fn call(&self, gas: u64, address: &Address, val: U256, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		let nested = self.begin("call", vec![Value::from(gas), bytes(address), u256(&val), bytes(input)]);
+		let outcome = self.inner.call(gas, address, val, input, result);
+		self.finish(nested, call_outcome(&outcome, result));
+		outcome
+	}
+
+	fn call_code(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		let nested = self.begin("call_code", vec![Value::from(gas), bytes(address), bytes(input)]);
+		let outcome = self.inner.call_code(gas, address, input, result);
+		self.finish(nested, call_outcome(&outcome, result));
+		outcome
+	}
+
+	fn static_call(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		let nested = self.begin("static_call", vec![Value::from(gas), bytes(address), bytes(input)]);
+		let outcome = self.inner.static_call(gas, address, input, result);
+		self.finish(nested, call_outcome(&outcome, result));
+		outcome
+	}
+
+	fn elog(&self, topics: &[H256], data: &[u8]) {
+		self.inner.elog(topics, data);
+		self.record("elog", vec![Value::Array(topics.iter().map(|t| bytes(t)).collect()), bytes(data)], Value::Null);
+	}
+
+	fn debug_log(&self, msg: String) {
+		self.inner.debug_log(msg.clone());
+		self.record("debug_log", vec![Value::String(msg)], Value::Null);
+	}
+
+	fn blockhash(&self, number: u64) -> Result<H256, Error> {
+		let result = self.inner.blockhash(number);
+		self.record("blockhash", vec![Value::from(number)], match result {
+			Ok(ref hash) => bytes(hash),
+			Err(_) => Value::Null,
+		});
+		result
+	}
+
+	fn coinbase(&self) -> Address {
+		let result = self.inner.coinbase();
+		self.record("coinbase", Vec::new(), bytes(&result));
+		result
+	}
+
+	fn timestamp(&self) -> u64 {
+		let result = self.inner.timestamp();
+		self.record("timestamp", Vec::new(), Value::from(result));
+		result
+	}
+
+	fn blocknumber(&self) -> u64 {
+		let result = self.inner.blocknumber();
+		self.record("blocknumber", Vec::new(), Value::from(result));
+		result
+	}
+
+	fn difficulty(&self) -> U256 {
+		let result = self.inner.difficulty();
+		self.record("difficulty", Vec::new(), u256(&result));
+		result
+	}
+
+	fn gas_limit(&self) -> U256 {
+		let result = self.inner.gas_limit();
+		self.record("gas_limit", Vec::new(), u256(&result));
+		result
+	}
+
+	fn sender(&self) -> Address {
+		let result = self.inner.sender();
+		self.record("sender", Vec::new(), bytes(&result));
+		result
+	}
+
+	fn origin(&self) -> Address {
+		let result = self.inner.origin();
+		self.record("origin", Vec::new(), bytes(&result));
+		result
+	}
+
+	fn value(&self) -> U256 {
+		let result = self.inner.value();
+		self.record("value", Vec::new(), u256(&result));
+		result
+	}
+
+	fn address(&self) -> Address {
+		let result = self.inner.address();
+		self.record("address", Vec::new(), bytes(&result));
+		result
+	}
+
+	fn as_any(&self) -> &Any {
+		self
+	}
+}
+
+/// `External` which answers with results saved by `Recorder`
+/// and panics on the first interaction which differs from the recording
+///
+/// Only interactions of the contract under test are replayed,
+/// the ones fired by endpoints during a `call` are skipped together with the endpoint.
+#[derive(Clone)]
+pub struct Replay {
+	interactions: Rc<Vec<Interaction>>,
+	position: Rc<Cell<usize>>,
+}
+
+impl Replay {
+	/// Creates replay from recorded interactions
+	pub fn new(interactions: Vec<Interaction>) -> Replay {
+		Replay {
+			interactions: Rc::new(interactions.into_iter().filter(|i| i.depth == 0).collect()),
+			position: Rc::new(Cell::new(0)),
+		}
+	}
+
+	/// Loads interactions saved with `Recorder::save`
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay, FixtureError> {
+		let mut recording = String::new();
+		File::open(path)?.read_to_string(&mut recording)?;
+		let mut interactions = Vec::new();
+		for line in recording.lines().filter(|l| !l.trim().is_empty()) {
+			let entry: Value = serde_json::from_str(line)?;
+			match Interaction::from_json(&entry) {
+				Some(interaction) => interactions.push(interaction),
+				None => return Err(FixtureError::Invalid(format!("{} is not a recorded interaction", line))),
+			}
+		}
+		Ok(Replay::new(interactions))
+	}
+
+	/// Returns number of recorded interactions which weren't replayed yet
+	pub fn remaining(&self) -> usize {
+		self.interactions.len() - self.position.get()
+	}
+
+	/// Returns `true` if every recorded interaction was replayed
+	pub fn is_finished(&self) -> bool {
+		self.remaining() == 0
+	}
+
+	fn replay(&self, name: &str, args: Vec<Value>) -> Value {
+		let position = self.position.get();
+		let expected = match self.interactions.get(position) {
+			Some(expected) => expected,
+			None => panic!("Replay diverged at interaction #{}: recording has ended, got {}({})",
+				position, name, display_args(&args)),
+		};
+		if expected.name != name || expected.args != args {
+			panic!("Replay diverged at interaction #{}: expected {}({}), got {}({})",
+				position, expected.name, display_args(&expected.args), name, display_args(&args));
+		}
+		self.position.set(position + 1);
+		expected.result.clone()
+	}
+}
+
+impl External for Replay {
+	fn balance(&self, address: &Address) -> U256 {
+		decode_u256(&self.replay("balance", vec![bytes(address)]))
+	}
+
+	fn storage_read(&self, key: &H256) -> [u8; 32] {
+		decode_word(&self.replay("storage_read", vec![bytes(key)]))
+	}
+
+	fn storage_write(&self, key: &H256, value: &[u8; 32]) {
+		self.replay("storage_write", vec![bytes(key), bytes(value)]);
+	}
+
+	fn suicide(&self, refund: &Address) {
+		self.replay("suicide", vec![bytes(refund)]);
+	}
+
+	fn create(&self, endowment: U256, code: &[u8]) -> Result<Address, Error> {
+		match self.replay("create", vec![u256(&endowment), bytes(code)]) {
+			Value::Null => Err(Error),
+			address => Ok(decode_address(&address)),
+		}
+	}
+
+	fn call(&self, gas: u64, address: &Address, val: U256, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		replay_call_outcome(&self.replay("call", vec![Value::from(gas), bytes(address), u256(&val), bytes(input)]), result)
+	}
+
+	fn call_code(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		replay_call_outcome(&self.replay("call_code", vec![Value::from(gas), bytes(address), bytes(input)]), result)
+	}
+
+	fn static_call(&self, gas: u64, address: &Address, input: &[u8], result: &mut [u8]) -> Result<(), Error> {
+		replay_call_outcome(&self.replay("static_call", vec![Value::from(gas), bytes(address), bytes(input)]), result)
+	}
+
+	fn elog(&self, topics: &[H256], data: &[u8]) {
+		self.replay("elog", vec![Value::Array(topics.iter().map(|t| bytes(t)).collect()), bytes(data)]);
+	}
+
+	fn debug_log(&self, msg: String) {
+		self.replay("debug_log", vec![Value::String(msg)]);
+	}
+
+	fn blockhash(&self, number: u64) -> Result<H256, Error> {
+		match self.replay("blockhash", vec![Value::from(number)]) {
+			Value::Null => Err(Error),
+			hash => Ok(H256::from_slice(&decode_word(&hash))),
+		}
+	}
+
+	fn coinbase(&self) -> Address {
+		decode_address(&self.replay("coinbase", Vec::new()))
+	}
+
+	fn timestamp(&self) -> u64 {
+		decode_u64(&self.replay("timestamp", Vec::new()))
+	}
+
+	fn blocknumber(&self) -> u64 {
+		decode_u64(&self.replay("blocknumber", Vec::new()))
+	}
+
+	fn difficulty(&self) -> U256 {
+		decode_u256(&self.replay("difficulty", Vec::new()))
+	}
+
+	fn gas_limit(&self) -> U256 {
+		decode_u256(&self.replay("gas_limit", Vec::new()))
+	}
+
+	fn sender(&self) -> Address {
+		decode_address(&self.replay("sender", Vec::new()))
+	}
+
+	fn origin(&self) -> Address {
+		decode_address(&self.replay("origin", Vec::new()))
+	}
+
+	fn value(&self) -> U256 {
+		decode_u256(&self.replay("value", Vec::new()))
+	}
+
+	fn address(&self) -> Address {
+		decode_address(&self.replay("address", Vec::new()))
+	}
+
+	fn as_any(&self) -> &Any {
+		self
+	}
+}
+
+const MALFORMED: &str = "Malformed recording";
+
+fn bytes(bytes: &[u8]) -> Value {
+	Value::String(hex::to_hex(bytes))
+}
+
+fn u256(value: &U256) -> Value {
+	Value::String(hex::u256_to_hex(value))
+}
+
+fn call_outcome(outcome: &Result<(), Error>, output: &[u8]) -> Value {
+	let mut result = Map::new();
+	result.insert("ok".to_owned(), Value::Bool(outcome.is_ok()));
+	result.insert("output".to_owned(), bytes(output));
+	Value::Object(result)
+}
+
+fn replay_call_outcome(outcome: &Value, result: &mut [u8]) -> Result<(), Error> {
+	let output = outcome.get("output").and_then(Value::as_str).and_then(hex::from_hex).expect(MALFORMED);
+	let len = cmp::min(output.len(), result.len());
+	result[..len].copy_from_slice(&output[..len]);
+	if outcome.get("ok").and_then(Value::as_bool).expect(MALFORMED) {
+		Ok(())
+	} else {
+		Err(Error)
+	}
+}
+
+fn display_args(args: &[Value]) -> String {
+	args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn decode_u64(value: &Value) -> u64 {
+	value.as_u64().expect(MALFORMED)
+}
+
+fn decode_u256(value: &Value) -> U256 {
+	value.as_str().and_then(hex::parse_u256).expect(MALFORMED)
+}
+
+fn decode_word(value: &Value) -> [u8; 32] {
+	value.as_str().and_then(hex::to_word).expect(MALFORMED)
+}
+
+fn decode_address(value: &Value) -> Address {
+	let address = value.as_str().and_then(hex::from_hex).expect(MALFORMED);
+	assert_eq!(address.len(), 20, "{}: {} is not an address", MALFORMED, value);
+	Address::from_slice(&address)
+}
@@ -0,0 +1,132 @@
+//! Typed storage words and Solidity-style slot computation
+//!
+//! ```
+//! # extern crate pwasm_test;
+//! # extern crate pwasm_ethereum;
+//! # use pwasm_test::{ext_reset, ext_get};
+//! # use pwasm_test::accounts::alice;
+//! # use pwasm_test::slots::{self, Word};
+//! # fn main () {
+//! #
+//!	// mapping(address => uint256) balances; at slot 1
+//!	let balance = slots::mapping(&slots::slot(1), &alice());
+//!	ext_reset(|e| e.storage_value(balance, 1000u64));
+//!	assert_eq!(ext_get().storage_u256(&balance), 1000.into());
+//!	assert_eq!(u64::from_word(&pwasm_ethereum::read(&balance)), 1000);
+//! # }
+//! ```
+use pwasm_std::types::{H256, U256, Address};
+use tiny_keccak::keccak256;
+
+use prelude::*;
+
+/// Value stored in a single 32-byte storage word
+pub trait Word: Sized {
+	/// Encodes the value as a word, numbers and addresses are right-aligned like in Solidity
+	fn to_word(&self) -> [u8; 32];
+	/// Decodes the value from a word, higher bytes of narrower types are ignored
+	fn from_word(word: &[u8; 32]) -> Self;
+}
+
+impl Word for [u8; 32] {
+	fn to_word(&self) -> [u8; 32] {
+		*self
+	}
+
+	fn from_word(word: &[u8; 32]) -> Self {
+		*word
+	}
+}
+
+impl Word for H256 {
+	fn to_word(&self) -> [u8; 32] {
+		let mut word = [0u8; 32];
+		word.copy_from_slice(self);
+		word
+	}
+
+	fn from_word(word: &[u8; 32]) -> Self {
+		H256::from_slice(word)
+	}
+}
+
+impl Word for U256 {
+	fn to_word(&self) -> [u8; 32] {
+		let mut word = [0u8; 32];
+		self.to_big_endian(&mut word);
+		word
+	}
+
+	fn from_word(word: &[u8; 32]) -> Self {
+		U256::from_big_endian(word)
+	}
+}
+
+impl Word for Address {
+	fn to_word(&self) -> [u8; 32] {
+		let mut word = [0u8; 32];
+		word[12..].copy_from_slice(self);
+		word
+	}
+
+	fn from_word(word: &[u8; 32]) -> Self {
+		Address::from_slice(&word[12..])
+	}
+}
+
+impl Word for u64 {
+	fn to_word(&self) -> [u8; 32] {
+		U256::from(*self).to_word()
+	}
+
+	fn from_word(word: &[u8; 32]) -> Self {
+		U256::from_word(word).low_u64()
+	}
+}
+
+impl Word for bool {
+	fn to_word(&self) -> [u8; 32] {
+		let mut word = [0u8; 32];
+		word[31] = *self as u8;
+		word
+	}
+
+	fn from_word(word: &[u8; 32]) -> Self {
+		word[31] != 0
+	}
+}
+
+/// Key of the `index`th declared storage slot
+pub fn slot(index: u64) -> H256 {
+	H256::from_word(&index.to_word())
+}
+
+/// Slot of `mapping[key]` for a mapping declared at `slot`: `keccak(key . slot)`
+pub fn mapping<K: Word>(slot: &H256, key: &K) -> H256 {
+	let mut preimage = key.to_word().to_vec();
+	preimage.extend_from_slice(slot);
+	H256::from_slice(&keccak256(&preimage))
+}
+
+/// Slot where elements of a dynamic array declared at `slot` start: `keccak(slot)`
+///
+/// The length of the array is stored at `slot` itself.
+pub fn array_start(slot: &H256) -> H256 {
+	H256::from_slice(&keccak256(slot))
+}
+
+/// Slot of `array[index]` for a dynamic array declared at `slot` with elements taking `element_words` words
+pub fn array_element(slot: &H256, index: u64, element_words: u64) -> H256 {
+	add_words(&array_start(slot), U256::from(index) * U256::from(element_words))
+}
+
+/// Slot `words` words after `slot`, e.g. a field of a struct stored at `slot`
+pub fn offset(slot: &H256, words: u64) -> H256 {
+	add_words(slot, U256::from(words))
+}
+
+/// Slot `words` words after `slot`, wrapping around like the EVM does
+fn add_words(slot: &H256, words: U256) -> H256 {
+	let (key, _) = U256::from_word(&slot.to_word()).overflowing_add(words);
+	H256::from_word(&key.to_word())
+}
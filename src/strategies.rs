@@ -0,0 +1,167 @@
+//! `proptest` strategies for pwasm types, ABI inputs and mocked worlds
+//!
+//! Available with both the `proptest` and `std` features, strategies use `std` collections.
+//!
+//! ```
+//! #[macro_use] extern crate proptest;
+//! extern crate pwasm_ethereum;
+//! extern crate pwasm_test;
+//!
+//! use pwasm_test::ext_reset;
+//! use pwasm_test::strategies::{address, u256};
+//!
+//! proptest! {
+//! 	#[test]
+//! 	fn balance_is_mocked(owner in address(), balance in u256()) {
+//! 		ext_reset(|e| e.balance_of(owner, balance));
+//! 		prop_assert_eq!(pwasm_ethereum::balance(&owner), balance);
+//! 	}
+//! }
+//! # fn main() {}
+//! ```
+use std::collections::HashMap;
+
+use proptest::prelude::*;
+use proptest::collection;
+use pwasm_std::types::{H256, U256, Address};
+
+use builder::ExternalBuilder;
+pub use abi::AbiArgs;
+
+/// Maximum number of generated storage slots and funded accounts
+const MAX_ENTRIES: usize = 16;
+/// Timestamp of the Ethereum genesis block
+const GENESIS_TIMESTAMP: u64 = 1_438_269_973;
+
+/// Any address
+pub fn address() -> BoxedStrategy<Address> {
+	any::<[u8; 20]>().prop_map(Address::from).boxed()
+}
+
+/// Any 32-byte hash
+pub fn h256() -> BoxedStrategy<H256> {
+	any::<[u8; 32]>().prop_map(|bytes| H256::from_slice(&bytes)).boxed()
+}
+
+/// `U256` biased towards edge cases: zero, one, maximum value, powers of two and their neighbours
+pub fn u256() -> BoxedStrategy<U256> {
+	prop_oneof![
+		1 => Just(U256::zero()),
+		1 => Just(U256::one()),
+		1 => Just(U256::max_value()),
+		2 => (0..256usize).prop_map(|shift| U256::one() << shift),
+		2 => (1..256usize).prop_map(|shift| (U256::one() << shift) - U256::one()),
+		3 => any::<u64>().prop_map(U256::from),
+		3 => any::<[u8; 32]>().prop_map(|bytes| U256::from_big_endian(&bytes)),
+	].boxed()
+}
+
+/// Any 32-byte storage value
+pub fn word() -> BoxedStrategy<[u8; 32]> {
+	u256().prop_map(|value| {
+		let mut word = [0u8; 32];
+		value.to_big_endian(&mut word);
+		word
+	}).boxed()
+}
+
+/// Storage of a single contract
+pub fn storage() -> BoxedStrategy<HashMap<H256, [u8; 32]>> {
+	collection::hash_map(h256(), word(), 0..MAX_ENTRIES).boxed()
+}
+
+/// Call input for a method with `selector`, arguments are generated by `args`
+///
+/// # Example
+/// ```
+/// # extern crate proptest;
+/// # extern crate pwasm_test;
+/// # use pwasm_test::strategies::{abi_input, address, u256};
+/// # fn main () {
+/// #
+///	// transfer(address,uint256)
+///	let transfer = abi_input([0xa9, 0x05, 0x9c, 0xbb], (address(), u256()));
+/// # }
+/// ```
+pub fn abi_input<S>(selector: [u8; 4], args: S) -> BoxedStrategy<Vec<u8>>
+	where S: Strategy + 'static, S::Value: AbiArgs
+{
+	args.prop_map(move |args| {
+		let mut input = selector.to_vec();
+		input.extend(args.encode());
+		input
+	}).boxed()
+}
+
+/// A valid mocked world, which can be applied to `ExternalBuilder`
+#[derive(Clone, Debug)]
+pub struct BuilderConfig {
+	pub sender: Address,
+	pub origin: Address,
+	pub address: Address,
+	pub coinbase: Address,
+	/// Never exceeds balance of `sender`
+	pub value: U256,
+	pub difficulty: U256,
+	pub gas_limit: U256,
+	pub blocknumber: u64,
+	pub timestamp: u64,
+	pub balances: HashMap<Address, U256>,
+	/// Storage of the contract at `address`
+	pub storage: HashMap<H256, [u8; 32]>,
+}
+
+impl BuilderConfig {
+	/// Applies generated world to `builder`
+	pub fn apply(&self, builder: ExternalBuilder) -> ExternalBuilder {
+		let mut builder = builder
+			.sender(self.sender)
+			.origin(self.origin)
+			.address(self.address)
+			.coinbase(self.coinbase)
+			.value(self.value)
+			.difficulty(self.difficulty)
+			.gas_limit(self.gas_limit)
+			.blocknumber(self.blocknumber)
+			.timestamp(self.timestamp);
+		for (address, balance) in &self.balances {
+			builder = builder.balance_of(*address, *balance);
+		}
+		for (key, value) in &self.storage {
+			builder = builder.storage(*key, *value);
+		}
+		builder
+	}
+}
+
+/// A valid mocked world: `value` is covered by the sender's balance,
+/// block context looks like the one of a real chain
+pub fn builder_config() -> BoxedStrategy<BuilderConfig> {
+	(
+		(address(), address(), address(), address()),
+		(u256(), 1..u64::max_value(), 5000..8_000_000u64),
+		(0..10_000_000u64, GENESIS_TIMESTAMP..2 * GENESIS_TIMESTAMP),
+		(collection::hash_map(address(), u256(), 0..MAX_ENTRIES), storage()),
+	).prop_map(|((sender, origin, address, coinbase), (value, difficulty, gas_limit), (blocknumber, timestamp), (mut balances, storage))| {
+		// The sender is always funded, so worlds don't depend on unfunded accounts reading as zero
+		{
+			let sender_balance = balances.entry(sender).or_insert(value);
+			if *sender_balance < value {
+				*sender_balance = value;
+			}
+		}
+		BuilderConfig {
+			sender: sender,
+			origin: origin,
+			address: address,
+			coinbase: coinbase,
+			value: value,
+			difficulty: U256::from(difficulty),
+			gas_limit: U256::from(gas_limit),
+			blocknumber: blocknumber,
+			timestamp: timestamp,
+			balances: balances,
+			storage: storage,
+		}
+	}).boxed()
+}
@@ -0,0 +1,354 @@
+//! Hierarchical trace of calls made in the mocked world
+use std::fmt;
+#[cfg(feature = "std")]
+use std::thread;
+
+use pwasm_std::types::{H256, U256, Address};
+
+use external::LogEntry;
+#[cfg(feature = "std")]
+use external::ExternalInstance;
+#[cfg(feature = "std")]
+use externs::try_get_external;
+#[cfg(feature = "std")]
+use coverage;
+use accounts;
+use hex;
+use prelude::*;
+
+/// Kind of a call frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+	Call,
+	Delegate,
+	Static,
+	Create,
+}
+
+impl Default for CallKind {
+	fn default() -> CallKind {
+		CallKind::Call
+	}
+}
+
+impl fmt::Display for CallKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			CallKind::Call => "CALL",
+			CallKind::Delegate => "DELEGATECALL",
+			CallKind::Static => "STATICCALL",
+			CallKind::Create => "CREATE",
+		})
+	}
+}
+
+/// Storage slot written inside a frame
+#[derive(Clone, Debug, Default)]
+pub struct StorageWrite {
+	/// Account the storage belongs to, differs from the frame's `to` for delegate calls
+	pub address: Address,
+	pub key: H256,
+	pub value: [u8; 32],
+}
+
+/// Position of a frame's log, storage write or nested call in execution order
+#[derive(Clone, Copy, Debug)]
+enum Event {
+	StorageWrite(usize),
+	Log(usize),
+	Call(usize),
+}
+
+/// Call frame with the frames it made
+#[derive(Clone, Debug, Default)]
+pub struct TraceFrame {
+	pub kind: CallKind,
+	pub from: Address,
+	pub to: Address,
+	pub value: U256,
+	pub gas: u64,
+	pub input: Vec<u8>,
+	pub output: Vec<u8>,
+	pub success: bool,
+	/// Why the frame failed, `None` if it succeeded
+	pub error: Option<String>,
+	/// Log entries fired by this frame, not including nested frames
+	pub logs: Vec<LogEntry>,
+	/// Storage written by this frame, not including nested frames
+	pub storage_writes: Vec<StorageWrite>,
+	/// Nested frames in the order they were made
+	pub calls: Vec<TraceFrame>,
+	events: Vec<Event>,
+	/// `true` for frames of transactions made with `ext_transact` rather than calls made by a contract
+	pub(crate) transaction: bool,
+	/// Number of the block a transaction was executed in
+	pub(crate) block: u64,
+	/// Hash identifying a transaction, see `TraceFrame::transaction_hash`
+	pub(crate) hash: H256,
+}
+
+impl TraceFrame {
+	/// Creates an unfinished frame
+	pub fn new(kind: CallKind, from: Address, to: Address, value: U256, gas: u64, input: &[u8]) -> TraceFrame {
+		TraceFrame {
+			kind: kind,
+			from: from,
+			to: to,
+			value: value,
+			gas: gas,
+			input: input.to_vec(),
+			..TraceFrame::default()
+		}
+	}
+
+	/// `true` if the frame is a transaction made with `ext_transact`
+	pub fn is_transaction(&self) -> bool {
+		self.transaction
+	}
+
+	/// Number of the block the transaction was executed in, zero for frames which aren't transactions
+	pub fn block(&self) -> u64 {
+		self.block
+	}
+
+	/// Hash identifying the transaction, zero for frames which aren't transactions
+	///
+	/// Transactions aren't signed, so this is keccak of the sender, its nonce, the recipient,
+	/// the value and the input rather than the hash a node would compute.
+	pub fn transaction_hash(&self) -> H256 {
+		self.hash
+	}
+
+	fn push_storage_write(&mut self, write: StorageWrite) {
+		self.events.push(Event::StorageWrite(self.storage_writes.len()));
+		self.storage_writes.push(write);
+	}
+
+	fn push_log(&mut self, entry: LogEntry) {
+		self.events.push(Event::Log(self.logs.len()));
+		self.logs.push(entry);
+	}
+
+	fn push_call(&mut self, frame: TraceFrame) {
+		self.events.push(Event::Call(self.calls.len()));
+		self.calls.push(frame);
+	}
+
+	fn render(&self, f: &mut fmt::Formatter, indent: &str) -> fmt::Result {
+		write!(f, "[{}] {} {} -> {}", self.gas, self.kind, accounts::format_address(&self.from), accounts::format_address(&self.to))?;
+		let method = accounts::format_method(&self.input);
+		if let Some(ref method) = method {
+			write!(f, ".{}", method)?;
+		}
+		if !self.value.is_zero() {
+			write!(f, " value {}", self.value)?;
+		}
+		if method.is_none() {
+			write!(f, " input {}", hex::to_hex(&self.input))?;
+		}
+		let nested = format!("{}│  ", indent);
+		for event in &self.events {
+			write!(f, "\n{}├─ ", indent)?;
+			match *event {
+				Event::StorageWrite(index) => {
+					let write = &self.storage_writes[index];
+					write!(f, "storage[{}] = {}", hex::to_hex(&write.key), hex::to_hex(&write.value))?;
+				},
+				Event::Log(index) => {
+					let log = &self.logs[index];
+					let topics: Vec<String> = log.topics.iter().map(|topic: &H256| hex::to_hex(topic)).collect();
+					write!(f, "emit topics [{}] data {}", topics.join(", "), hex::to_hex(&log.data))?;
+				},
+				Event::Call(index) => self.calls[index].render(f, &nested)?,
+			}
+		}
+		match self.error {
+			None => write!(f, "\n{}└─ ← {}", indent, hex::to_hex(&self.output)),
+			Some(ref error) => write!(f, "\n{}└─ ← {}", indent, error),
+		}
+	}
+}
+
+/// Renders the frame like `cast run` does, e.g. for the `display` test in `tests/trace.rs`:
+///
+/// ```text
+/// [1000000] CALL alice -> token input 0x0102
+/// ├─ [2000] CALL token -> oracle input 0x0102
+/// │  ├─ storage[0x0000000000000000000000000000000000000000000000000000000000000000] = 0x0101010101010101010101010101010101010101010101010101010101010101
+/// │  └─ ← 0xba975da4d2459aa3f11d31291a25a8358f84946d
+/// ├─ storage[0x0000000000000000000000000000000000000000000000000000000000000000] = 0x000000000000000000000000ba975da4d2459aa3f11d31291a25a8358f84946d
+/// ├─ emit topics [0x0000000000000000000000000000000000000000000000000000000000000000] data 0xba975da4d2459aa3f11d31291a25a8358f84946d
+/// └─ ← 0xba975da4d2459aa3f11d31291a25a8358f84946d
+/// ```
+///
+/// Accounts are shown by label or address book name, methods registered with `ExternalBuilder::signature` are decoded.
+impl fmt::Display for TraceFrame {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.render(f, "")
+	}
+}
+
+/// Execution context of a frame
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct Context {
+	pub sender: Address,
+	pub address: Address,
+	pub value: U256,
+}
+
+#[doc(hidden)]
+/// Records frames entered by `ExternalInstance`
+#[derive(Clone, Debug, Default)]
+pub struct Tracer {
+	/// Finished top-level frames
+	frames: Vec<TraceFrame>,
+	/// Unfinished frames, innermost last
+	stack: Vec<(Context, TraceFrame)>,
+}
+
+impl Tracer {
+	/// Starts a nested frame executing in `context`
+	pub fn enter(&mut self, context: Context, frame: TraceFrame) {
+		self.stack.push((context, frame));
+	}
+
+	/// Finishes the innermost frame
+	pub fn exit(&mut self, output: &[u8], error: Option<String>) {
+		let (_, mut frame) = self.stack.pop().expect("Every exited frame is entered first; qed");
+		frame.output = output.to_vec();
+		frame.success = error.is_none();
+		frame.error = error;
+		match self.stack.last_mut() {
+			Some(&mut (_, ref mut parent)) => parent.push_call(frame),
+			None => {
+				record_coverage(&frame);
+				self.frames.push(frame);
+			},
+		}
+	}
+
+	/// Context of the innermost frame, `None` outside of any frame
+	pub fn context(&self) -> Option<Context> {
+		self.stack.last().map(|&(context, _)| context)
+	}
+
+	/// Sender of the transaction in progress, `None` outside of transactions made with `ext_transact`
+	pub fn origin(&self) -> Option<Address> {
+		self.stack.first().and_then(|&(_, ref frame)| if frame.transaction { Some(frame.from) } else { None })
+	}
+
+	pub fn storage_write(&mut self, address: Address, key: H256, value: [u8; 32]) {
+		if let Some(&mut (_, ref mut frame)) = self.stack.last_mut() {
+			frame.push_storage_write(StorageWrite {
+				address: address,
+				key: key,
+				value: value,
+			});
+		}
+	}
+
+	pub fn log(&mut self, entry: LogEntry) {
+		if let Some(&mut (_, ref mut frame)) = self.stack.last_mut() {
+			frame.push_log(entry);
+		}
+	}
+
+	/// Finished top-level frames
+	pub fn frames(&self) -> &[TraceFrame] {
+		&self.frames
+	}
+
+	/// Frame of the transaction in progress with its unfinished frames closed, or the last finished frame
+	pub fn latest(&self) -> Option<TraceFrame> {
+		let mut unfinished = self.stack.iter().rev().map(|&(_, ref frame)| frame.clone());
+		match unfinished.next() {
+			Some(innermost) => Some(unfinished.fold(close(innermost), |frame, mut parent| {
+				parent.push_call(frame);
+				close(parent)
+			})),
+			None => self.frames.last().cloned(),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+fn record_coverage(frame: &TraceFrame) {
+	coverage::record(frame);
+}
+
+/// ABI coverage is only reported by processes with `std`
+#[cfg(not(feature = "std"))]
+fn record_coverage(_frame: &TraceFrame) {}
+
+fn close(mut frame: TraceFrame) -> TraceFrame {
+	frame.error = Some("unfinished".to_owned());
+	frame
+}
+
+/// Prints traces of finished transactions if the thread panics before the printer is dropped,
+/// see `#[pwasm_test(print_trace)]`
+///
+/// The transaction a panic unwinds through is printed anyway, by the `UnwindPrinter` of its frame.
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct TracePrinter;
+
+#[cfg(feature = "std")]
+impl TracePrinter {
+	pub fn new() -> TracePrinter {
+		TracePrinter
+	}
+}
+
+#[cfg(feature = "std")]
+impl Drop for TracePrinter {
+	fn drop(&mut self) {
+		if !thread::panicking() {
+			return;
+		}
+		if let Ok(instance) = try_get_external::<ExternalInstance>() {
+			for (index, trace) in instance.traces().iter().enumerate() {
+				eprintln!("Trace of transaction #{}:\n{}", index, trace);
+			}
+		}
+	}
+}
+
+/// Prints the trace of a top-level frame if a panic unwinds through it, so tests failing
+/// inside a transaction or a call show where it happened
+///
+/// Panics caught by the test, e.g. with `should_panic`, are printed too, but the test harness
+/// only shows the output of failing tests.
+#[cfg(feature = "std")]
+pub(crate) struct UnwindPrinter {
+	top_level: bool,
+}
+
+#[cfg(feature = "std")]
+impl UnwindPrinter {
+	/// Creates a printer for a frame about to be entered by `tracer`
+	pub(crate) fn new(tracer: &Tracer) -> UnwindPrinter {
+		UnwindPrinter {
+			top_level: tracer.stack.is_empty(),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl Drop for UnwindPrinter {
+	fn drop(&mut self) {
+		if !self.top_level || !thread::panicking() {
+			return;
+		}
+		if let Ok(instance) = try_get_external::<ExternalInstance>() {
+			let latest = match instance.trace.try_borrow() {
+				Ok(tracer) => tracer.latest(),
+				Err(_) => None,
+			};
+			if let Some(trace) = latest {
+				eprintln!("Trace of the frame which panicked:\n{}", trace);
+			}
+		}
+	}
+}
@@ -0,0 +1,420 @@
+//! Execution of compiled contract `.wasm` binaries against the mocked externs
+//!
+//! Imports of the `env` module are resolved onto the same `External` which is installed
+//! with `set_external`, so a contract artifact can be tested exactly like a natively linked one.
+use std::fmt;
+use std::error;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::cell::RefCell;
+
+use wasmi::{self, Module, ModuleInstance, ModuleRef, ImportsBuilder, ModuleImportResolver, Externals,
+	RuntimeArgs, RuntimeValue, FuncRef, FuncInstance, Signature, ValueType, MemoryRef, MemoryInstance,
+	MemoryDescriptor, Trap, TrapKind, HostError};
+use wasmi::memory_units::Pages;
+use pwasm_std::types::{H256, U256, Address};
+
+use external::{Endpoint, Error};
+use externs::with_external;
+
+/// Error while loading or running a contract binary
+#[derive(Debug)]
+pub enum WasmError {
+	/// Binary can't be read
+	Io(io::Error),
+	/// Binary is invalid, has unknown imports or execution trapped
+	Interpreter(wasmi::Error),
+	/// Contract called `panic` with a message
+	Panic(String),
+}
+
+impl From<io::Error> for WasmError {
+	fn from(err: io::Error) -> WasmError {
+		WasmError::Io(err)
+	}
+}
+
+impl From<wasmi::Error> for WasmError {
+	fn from(err: wasmi::Error) -> WasmError {
+		WasmError::Interpreter(err)
+	}
+}
+
+impl fmt::Display for WasmError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			WasmError::Io(ref err) => write!(f, "Can't read contract binary: {}", err),
+			WasmError::Interpreter(ref err) => write!(f, "Interpreter error: {}", err),
+			WasmError::Panic(ref msg) => write!(f, "Contract panicked: {}", msg),
+		}
+	}
+}
+
+impl error::Error for WasmError {
+	fn description(&self) -> &str {
+		"contract binary execution failed"
+	}
+}
+
+/// Host functions available to contracts, the index in this table is the function index
+const HOST_FUNCS: &[(&str, &[ValueType], Option<ValueType>)] = &[
+	("storage_read", &[ValueType::I32, ValueType::I32], None),
+	("storage_write", &[ValueType::I32, ValueType::I32], None),
+	("create", &[ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32], Some(ValueType::I32)),
+	("ccall", &[ValueType::I64, ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32], Some(ValueType::I32)),
+	("dcall", &[ValueType::I64, ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32], Some(ValueType::I32)),
+	("scall", &[ValueType::I64, ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32], Some(ValueType::I32)),
+	("suicide", &[ValueType::I32], None),
+	("blockhash", &[ValueType::I64, ValueType::I32], Some(ValueType::I32)),
+	("coinbase", &[ValueType::I32], None),
+	("timestamp", &[], Some(ValueType::I64)),
+	("blocknumber", &[], Some(ValueType::I64)),
+	("difficulty", &[ValueType::I32], None),
+	("gaslimit", &[ValueType::I32], None),
+	("sender", &[ValueType::I32], None),
+	("address", &[ValueType::I32], None),
+	("value", &[ValueType::I32], None),
+	("origin", &[ValueType::I32], None),
+	("balance", &[ValueType::I32, ValueType::I32], None),
+	("debug", &[ValueType::I32, ValueType::I32], None),
+	("elog", &[ValueType::I32, ValueType::I32, ValueType::I32, ValueType::I32], None),
+	("ret", &[ValueType::I32, ValueType::I32], None),
+	("input_length", &[], Some(ValueType::I32)),
+	("fetch_input", &[ValueType::I32], None),
+	("panic", &[ValueType::I32, ValueType::I32], None),
+	("gas", &[ValueType::I32], None),
+];
+
+/// A compiled contract binary
+///
+/// Every `deploy` and `call` runs in a fresh instance, the state lives in the installed `External`.
+///
+/// # Example
+/// ```no_run
+/// # extern crate pwasm_test;
+/// # use pwasm_test::{ext_reset, ext_transact, WasmContract};
+/// # fn main () {
+/// #
+///	let contract = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+///	let wasm = WasmContract::load("target/pwasm/token.wasm").unwrap();
+///	ext_reset(|e| e.endpoint(contract, wasm.clone().into()));
+///	let receipt = ext_transact("16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap(), contract, 0.into(), &[]);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct WasmContract {
+	code: Vec<u8>,
+}
+
+impl WasmContract {
+	/// Loads contract binary from a file
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<WasmContract, WasmError> {
+		let mut code = Vec::new();
+		File::open(path)?.read_to_end(&mut code)?;
+		WasmContract::from_bytes(&code)
+	}
+
+	/// Creates contract from binary code
+	pub fn from_bytes(code: &[u8]) -> Result<WasmContract, WasmError> {
+		Module::from_buffer(code)?;
+		Ok(WasmContract { code: code.to_vec() })
+	}
+
+	/// Runs `deploy` export with `input`, returns data passed to `ret`
+	/// which for binaries packed by `wasm-build` is the code of the runtime contract
+	pub fn deploy(&self, input: &[u8]) -> Result<Vec<u8>, WasmError> {
+		self.invoke("deploy", input)
+	}
+
+	/// Runs `call` export with `input`, returns data passed to `ret`
+	pub fn call(&self, input: &[u8]) -> Result<Vec<u8>, WasmError> {
+		self.invoke("call", input)
+	}
+
+	fn invoke(&self, export: &str, input: &[u8]) -> Result<Vec<u8>, WasmError> {
+		let module = Module::from_buffer(&self.code)?;
+		let resolver = Resolver::default();
+		let instance = ModuleInstance::new(&module, &ImportsBuilder::new().with_resolver("env", &resolver))?;
+		let memory = resolver.memory.borrow().clone()
+			.or_else(|| exported_memory(instance.not_started_instance()));
+		let mut runtime = Runtime {
+			memory: memory,
+			input: input,
+			result: Vec::new(),
+			halt: None,
+		};
+		let outcome = instance.run_start(&mut runtime)
+			.map_err(wasmi::Error::Trap)
+			.and_then(|instance| instance.invoke_export(export, &[], &mut runtime));
+		match (outcome, runtime.halt) {
+			(_, Some(Halt::Panic(msg))) => Err(WasmError::Panic(msg)),
+			(_, Some(Halt::Return)) | (Ok(_), None) => Ok(runtime.result),
+			(Err(err), None) => Err(WasmError::Interpreter(err)),
+		}
+	}
+}
+
+/// Wraps contract binary to `Endpoint`, failing the call if the contract panics or traps
+impl From<WasmContract> for Endpoint {
+	fn from(contract: WasmContract) -> Endpoint {
+		Endpoint::returning(Box::new(move |_val, input| {
+			contract.call(input).map_err(|_| Error)
+		}))
+	}
+}
+
+fn exported_memory(instance: &ModuleRef) -> Option<MemoryRef> {
+	instance.export_by_name("memory").and_then(|export| export.as_memory().cloned())
+}
+
+#[derive(Default)]
+struct Resolver {
+	memory: RefCell<Option<MemoryRef>>,
+}
+
+impl ModuleImportResolver for Resolver {
+	fn resolve_func(&self, field_name: &str, signature: &Signature) -> Result<FuncRef, wasmi::Error> {
+		let index = match HOST_FUNCS.iter().position(|&(name, _, _)| name == field_name) {
+			Some(index) => index,
+			None => return Err(wasmi::Error::Instantiation(format!("Unknown import env.{}", field_name))),
+		};
+		let (_, params, result) = HOST_FUNCS[index];
+		if signature.params() != params || signature.return_type() != result {
+			return Err(wasmi::Error::Instantiation(
+				format!("Import env.{} has signature {:?}, expected {:?} -> {:?}", field_name, signature, params, result)
+			));
+		}
+		Ok(FuncInstance::alloc_host(Signature::new(params, result), index))
+	}
+
+	fn resolve_memory(&self, field_name: &str, descriptor: &MemoryDescriptor) -> Result<MemoryRef, wasmi::Error> {
+		if field_name != "memory" {
+			return Err(wasmi::Error::Instantiation(format!("Unknown memory import env.{}", field_name)));
+		}
+		let memory = MemoryInstance::alloc(
+			Pages(descriptor.initial() as usize),
+			descriptor.maximum().map(|maximum| Pages(maximum as usize)),
+		)?;
+		*self.memory.borrow_mut() = Some(memory.clone());
+		Ok(memory)
+	}
+}
+
+/// Reason execution was stopped by a host function
+enum Halt {
+	Return,
+	Panic(String),
+}
+
+#[derive(Debug)]
+struct HaltTrap;
+
+impl fmt::Display for HaltTrap {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Execution halted")
+	}
+}
+
+impl HostError for HaltTrap {}
+
+struct Runtime<'a> {
+	memory: Option<MemoryRef>,
+	input: &'a [u8],
+	result: Vec<u8>,
+	halt: Option<Halt>,
+}
+
+impl<'a> Runtime<'a> {
+	fn memory(&self) -> Result<&MemoryRef, Trap> {
+		self.memory.as_ref().ok_or_else(|| Trap::new(TrapKind::MemoryAccessOutOfBounds))
+	}
+
+	fn read(&self, ptr: u32, len: usize) -> Result<Vec<u8>, Trap> {
+		self.memory()?.get(ptr, len).map_err(|_| Trap::new(TrapKind::MemoryAccessOutOfBounds))
+	}
+
+	fn write(&self, ptr: u32, data: &[u8]) -> Result<(), Trap> {
+		self.memory()?.set(ptr, data).map_err(|_| Trap::new(TrapKind::MemoryAccessOutOfBounds))
+	}
+
+	fn read_address(&self, ptr: u32) -> Result<Address, Trap> {
+		Ok(Address::from_slice(&self.read(ptr, 20)?))
+	}
+
+	fn read_h256(&self, ptr: u32) -> Result<H256, Trap> {
+		Ok(H256::from_slice(&self.read(ptr, 32)?))
+	}
+
+	fn read_u256(&self, ptr: u32) -> Result<U256, Trap> {
+		Ok(U256::from_big_endian(&self.read(ptr, 32)?))
+	}
+
+	fn write_u256(&self, ptr: u32, value: U256) -> Result<(), Trap> {
+		let mut bytes = [0u8; 32];
+		value.to_big_endian(&mut bytes);
+		self.write(ptr, &bytes)
+	}
+
+	fn halt(&mut self, halt: Halt) -> Trap {
+		self.halt = Some(halt);
+		Trap::new(TrapKind::Host(Box::new(HaltTrap)))
+	}
+
+	/// Runs `ccall`, `dcall` or `scall`, only the first one has a value argument
+	fn call(&self, kind: CallKind, args: &RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+		let offset = if kind == CallKind::Call { 1 } else { 0 };
+		let gas: u64 = args.nth_checked(0)?;
+		let address = self.read_address(args.nth_checked(1)?)?;
+		let input_ptr: u32 = args.nth_checked(2 + offset)?;
+		let input_len: u32 = args.nth_checked(3 + offset)?;
+		let result_ptr: u32 = args.nth_checked(4 + offset)?;
+		let result_len: u32 = args.nth_checked(5 + offset)?;
+		let input = self.read(input_ptr, input_len as usize)?;
+		let mut result = vec![0u8; result_len as usize];
+		let outcome = match kind {
+			CallKind::Call => {
+				let val = self.read_u256(args.nth_checked(2)?)?;
+				with_external(|e| e.call(gas, &address, val, &input, &mut result))
+			},
+			CallKind::Delegate => with_external(|e| e.call_code(gas, &address, &input, &mut result)),
+			CallKind::Static => with_external(|e| e.static_call(gas, &address, &input, &mut result)),
+		};
+		self.write(result_ptr, &result)?;
+		Ok(Some(RuntimeValue::I32(if outcome.is_ok() { 0 } else { 1 })))
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CallKind {
+	Call,
+	Delegate,
+	Static,
+}
+
+impl<'a> Externals for Runtime<'a> {
+	fn invoke_index(&mut self, index: usize, args: RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+		match HOST_FUNCS[index].0 {
+			"storage_read" => {
+				let key = self.read_h256(args.nth_checked(0)?)?;
+				let value = with_external(|e| e.storage_read(&key));
+				self.write(args.nth_checked(1)?, &value)?;
+				Ok(None)
+			},
+			"storage_write" => {
+				let key = self.read_h256(args.nth_checked(0)?)?;
+				let mut value = [0u8; 32];
+				value.copy_from_slice(&self.read(args.nth_checked(1)?, 32)?);
+				with_external(|e| e.storage_write(&key, &value));
+				Ok(None)
+			},
+			"create" => {
+				let endowment = self.read_u256(args.nth_checked(0)?)?;
+				let code_ptr: u32 = args.nth_checked(1)?;
+				let code_len: u32 = args.nth_checked(2)?;
+				let code = self.read(code_ptr, code_len as usize)?;
+				match with_external(|e| e.create(endowment, &code)) {
+					Ok(address) => {
+						self.write(args.nth_checked(3)?, &address)?;
+						Ok(Some(RuntimeValue::I32(0)))
+					},
+					Err(_) => Ok(Some(RuntimeValue::I32(1))),
+				}
+			},
+			"ccall" => self.call(CallKind::Call, &args),
+			"dcall" => self.call(CallKind::Delegate, &args),
+			"scall" => self.call(CallKind::Static, &args),
+			"suicide" => {
+				let refund = self.read_address(args.nth_checked(0)?)?;
+				with_external(|e| e.suicide(&refund));
+				Ok(None)
+			},
+			"blockhash" => {
+				let number: u64 = args.nth_checked(0)?;
+				match with_external(|e| e.blockhash(number)) {
+					Ok(hash) => {
+						self.write(args.nth_checked(1)?, &hash)?;
+						Ok(Some(RuntimeValue::I32(0)))
+					},
+					Err(_) => Ok(Some(RuntimeValue::I32(1))),
+				}
+			},
+			"coinbase" => {
+				let coinbase = with_external(|e| e.coinbase());
+				self.write(args.nth_checked(0)?, &coinbase)?;
+				Ok(None)
+			},
+			"timestamp" => Ok(Some(RuntimeValue::I64(with_external(|e| e.timestamp()) as i64))),
+			"blocknumber" => Ok(Some(RuntimeValue::I64(with_external(|e| e.blocknumber()) as i64))),
+			"difficulty" => {
+				let difficulty = with_external(|e| e.difficulty());
+				self.write_u256(args.nth_checked(0)?, difficulty)?;
+				Ok(None)
+			},
+			"gaslimit" => {
+				let gas_limit = with_external(|e| e.gas_limit());
+				self.write_u256(args.nth_checked(0)?, gas_limit)?;
+				Ok(None)
+			},
+			"sender" => {
+				let sender = with_external(|e| e.sender());
+				self.write(args.nth_checked(0)?, &sender)?;
+				Ok(None)
+			},
+			"address" => {
+				let address = with_external(|e| e.address());
+				self.write(args.nth_checked(0)?, &address)?;
+				Ok(None)
+			},
+			"value" => {
+				let value = with_external(|e| e.value());
+				self.write_u256(args.nth_checked(0)?, value)?;
+				Ok(None)
+			},
+			"origin" => {
+				let origin = with_external(|e| e.origin());
+				self.write(args.nth_checked(0)?, &origin)?;
+				Ok(None)
+			},
+			"balance" => {
+				let address = self.read_address(args.nth_checked(0)?)?;
+				let balance = with_external(|e| e.balance(&address));
+				self.write_u256(args.nth_checked(1)?, balance)?;
+				Ok(None)
+			},
+			"debug" => {
+				let len: u32 = args.nth_checked(1)?;
+				let msg = String::from_utf8_lossy(&self.read(args.nth_checked(0)?, len as usize)?).into_owned();
+				with_external(|e| e.debug_log(msg));
+				Ok(None)
+			},
+			"elog" => {
+				let topic_count: u32 = args.nth_checked(1)?;
+				let topics = self.read(args.nth_checked(0)?, topic_count as usize * 32)?;
+				let topics: Vec<H256> = topics.chunks(32).map(H256::from_slice).collect();
+				let data_len: u32 = args.nth_checked(3)?;
+				let data = self.read(args.nth_checked(2)?, data_len as usize)?;
+				with_external(|e| e.elog(&topics, &data));
+				Ok(None)
+			},
+			"ret" => {
+				let len: u32 = args.nth_checked(1)?;
+				self.result = self.read(args.nth_checked(0)?, len as usize)?;
+				Err(self.halt(Halt::Return))
+			},
+			"input_length" => Ok(Some(RuntimeValue::I32(self.input.len() as i32))),
+			"fetch_input" => {
+				let input = self.input;
+				self.write(args.nth_checked(0)?, input)?;
+				Ok(None)
+			},
+			"panic" => {
+				let len: u32 = args.nth_checked(1)?;
+				let msg = String::from_utf8_lossy(&self.read(args.nth_checked(0)?, len as usize)?).into_owned();
+				Err(self.halt(Halt::Panic(msg)))
+			},
+			"gas" => Ok(None),
+			name => unreachable!("Host function {} is in HOST_FUNCS; qed", name),
+		}
+	}
+}
@@ -0,0 +1,135 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_transact, ext_get, contract_address, CallKind, Endpoint, Error};
+
+fn owner() -> Address {
+	"6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0".parse().unwrap()
+}
+
+fn factory() -> Address {
+	"35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap()
+}
+
+#[test]
+fn contract_address_is_derived_from_creator_and_nonce() {
+	assert_eq!(contract_address(&owner(), &0.into()), "cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d".parse().unwrap());
+	assert_eq!(contract_address(&owner(), &1.into()), "343c43a37d37dff08ae8c4a11544c718abb4fcf8".parse().unwrap());
+}
+
+#[test]
+fn transactions_increment_sender_nonce() {
+	ext_reset(|e| e
+		.balance_of(owner(), 1000.into())
+		.nonce(owner(), 2.into())
+		.endpoint(factory(), Endpoint::err())
+	);
+	assert!(ext_transact(owner(), owner(), 0.into(), &[]).status);
+	assert!(!ext_transact(owner(), factory(), 0.into(), &[]).status);
+	assert_eq!(ext_get().nonce(&owner()), 4.into());
+	assert_eq!(ext_get().nonce(&factory()), 0.into());
+}
+
+#[test]
+fn create_deploys_code_at_derived_address() {
+	ext_reset(|e| e
+		.address(owner())
+		.balance_of(owner(), 1000.into())
+	);
+	let first = pwasm_ethereum::create(100.into(), &[0, 0x61, 0x73, 0x6d]).unwrap();
+	let second = pwasm_ethereum::create(0.into(), &[1]).unwrap();
+
+	let instance = ext_get();
+	assert_eq!(first, contract_address(&owner(), &0.into()));
+	assert_eq!(second, contract_address(&owner(), &1.into()));
+	assert_eq!(instance.nonce(&owner()), 2.into());
+	assert_eq!(instance.code_at(&first), vec![0, 0x61, 0x73, 0x6d]);
+	assert!(instance.is_contract(&first));
+	assert!(!instance.is_contract(&owner()));
+	assert_eq!(pwasm_ethereum::balance(&owner()), 900.into());
+	assert_eq!(pwasm_ethereum::balance(&first), 100.into());
+
+	let trace = &instance.traces()[0];
+	assert_eq!(trace.kind, CallKind::Create);
+	assert_eq!(trace.from, owner());
+	assert_eq!(trace.to, first);
+}
+
+#[test]
+fn create_fails_without_endowment() {
+	ext_reset(|e| e.address(owner()).balance_of(owner(), 10.into()));
+	assert!(pwasm_ethereum::create(100.into(), &[1]).is_err());
+	assert_eq!(ext_get().nonce(&owner()), 0.into());
+	assert!(ext_get().traces()[0].error.is_some());
+}
+
+#[test]
+fn contracts_created_by_reverted_transaction_are_dropped() {
+	ext_reset(|e| e
+		.balance_of(owner(), 1000.into())
+		.endpoint(factory(), Endpoint::returning(Box::new(|_val, _input| {
+			pwasm_ethereum::create(0.into(), &[1]).map_err(|_| Error)?;
+			Err(Error)
+		})))
+	);
+	assert!(!ext_transact(owner(), factory(), 0.into(), &[]).status);
+	let created = contract_address(&factory(), &0.into());
+	assert!(!ext_get().is_contract(&created));
+	assert_eq!(ext_get().nonce(&factory()), 0.into());
+}
+
+#[test]
+fn code_hash_defaults_to_keccak_of_code() {
+	ext_reset(|e| e.code(factory(), vec![]).endpoint(owner(), Endpoint::ok()));
+	let empty_hash: H256 = "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470".parse().unwrap();
+	assert_eq!(ext_get().code_hash(&factory()), empty_hash);
+	assert!(!ext_get().is_contract(&factory()));
+	assert!(ext_get().is_contract(&owner()));
+}
+
+#[test]
+fn book_accounts_are_derived_from_names() {
+	use pwasm_test::accounts::{self, alice, bob};
+	assert_eq!(alice(), accounts::named("alice"));
+	assert_eq!(accounts::account(1), bob());
+	assert_eq!(accounts::name(9), "account9");
+	assert_ne!(alice(), bob());
+	assert_eq!(accounts::format_address(&bob()), "bob");
+	assert_eq!(accounts::format_address(&owner()), "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0");
+}
+
+#[test]
+fn funded_accounts_are_named() {
+	use pwasm_test::accounts::{self, alice};
+	ext_reset(|e| e.funded_accounts(10, 5.into()));
+	assert_eq!(pwasm_ethereum::balance(&alice()), 5.into());
+	assert_eq!(pwasm_ethereum::balance(&accounts::account(9)), 5.into());
+	assert_eq!(accounts::format_address(&accounts::account(9)), "account9");
+}
+
+#[test]
+fn trace_shows_names_and_decoded_methods() {
+	use pwasm_test::accounts::{alice, bob};
+	use pwasm_test::{selector, AbiArgs};
+	use pwasm_std::types::U256;
+
+	ext_reset(|e| e
+		.funded_accounts(2, 1000.into())
+		.label(factory(), "token")
+		.signature("transfer(address,uint256)")
+		.endpoint(factory(), Endpoint::ok())
+	);
+	let mut input = selector("transfer(address,uint256)").to_vec();
+	input.extend((bob(), U256::from(100)).encode());
+	let receipt = ext_transact(alice(), factory(), 0.into(), &input);
+	let rendered = receipt.trace.to_string();
+	assert!(rendered.starts_with("[0] CALL alice -> token.transfer(bob, 100)\n"), rendered);
+}
+
+#[test]
+fn balance_of_unfunded_account_is_zero() {
+	ext_reset(|e| e);
+	assert_eq!(pwasm_ethereum::balance(&contract_address(&owner(), &0.into())), 0.into());
+}
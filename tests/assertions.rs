@@ -0,0 +1,67 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate tiny_keccak;
+
+use std::panic;
+
+use pwasm_std::types::{H256, U256, Address};
+use pwasm_test::{assert_event_eq, assert_log_eq, assert_call_eq, assert_word_eq, diff_bytes, AbiArgs, Call, LogEntry};
+use pwasm_test::accounts;
+use tiny_keccak::keccak256;
+
+fn owner() -> Address {
+	accounts::alice()
+}
+
+fn transfer(amount: u64) -> LogEntry {
+	let mut to = [0u8; 32];
+	to[12..].copy_from_slice(&owner());
+	LogEntry::new(
+		&[
+			H256::from_slice(&keccak256(b"Transfer(address,address,uint256)")),
+			H256::zero(),
+			H256::from_slice(&to),
+		],
+		&(U256::from(amount),).encode(),
+	)
+}
+
+fn panic_message<F: FnOnce() + panic::UnwindSafe>(f: F) -> String {
+	let payload = panic::catch_unwind(f).unwrap_err();
+	payload.downcast_ref::<String>().cloned().unwrap()
+}
+
+#[test]
+fn equal_entries_pass() {
+	assert_log_eq(&transfer(10), &transfer(10));
+	assert_call_eq(&Call::default(), &Call::default());
+	assert_word_eq(&[1; 32], &[1; 32]);
+	assert_eq!(diff_bytes(&[1, 2], &[1, 2]), None);
+}
+
+#[test]
+fn diff_marks_differing_words() {
+	let mut actual = vec![0u8; 64];
+	actual[40] = 1;
+	let diff = diff_bytes(&actual, &[0u8; 64]).unwrap();
+	let lines: Vec<&str> = diff.lines().collect();
+	assert_eq!(lines[0], "differ from byte 40 (word 1), expected 64 bytes, actual 64 bytes");
+	assert!(lines[2].starts_with("    0 "));
+	assert!(lines[3].starts_with("  > 1 "));
+}
+
+#[test]
+fn event_diff_shows_decoded_arguments() {
+	let message = panic_message(|| assert_event_eq(&transfer(50), &transfer(100),
+		"Transfer(address indexed from, address indexed to, uint256 value)"));
+	assert!(message.starts_with("Transfer log entries differ:\n  data differ from byte 31 (word 0)"));
+	assert!(message.contains("expected Transfer(0x0000000000000000000000000000000000000000, alice, 100)"));
+	assert!(message.contains("actual   Transfer(0x0000000000000000000000000000000000000000, alice, 50)"));
+}
+
+#[test]
+fn call_diff_lists_fields() {
+	let expected = Call { value: 5.into(), ..Call::default() };
+	let message = panic_message(|| assert_call_eq(&Call::default(), &expected));
+	assert_eq!(message, "Calls differ:\n  value: expected 5, actual 0\n");
+}
@@ -0,0 +1,76 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{H256, U256, Address};
+use pwasm_test::{pwasm_test, ext_get, ext_transact, ext_update};
+use pwasm_test::layout::Layout;
+
+fn owner() -> Address {
+	"16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap()
+}
+
+fn slot(index: u8) -> H256 {
+	let mut key = [0u8; 32];
+	key[31] = index;
+	H256::from_slice(&key)
+}
+
+#[pwasm_test(
+	sender = "0x16a0772b17ae004e6645e0e95bf50ad69498a34e",
+	address = "0x35da6abcb08f2b6164fe380bb6c47bd8f2304d55",
+	value = 100,
+	gas_limit = "0x2710",
+	blocknumber = 7,
+	balance("0x16a0772b17ae004e6645e0e95bf50ad69498a34e" = "1000000000000000000000"),
+	nonce("0x16a0772b17ae004e6645e0e95bf50ad69498a34e" = 3),
+	storage("0x01" = 7, "0x02" = "0xff"),
+)]
+fn declarative_setup() {
+	assert_eq!(pwasm_ethereum::sender(), owner());
+	assert_eq!(pwasm_ethereum::value(), 100.into());
+	assert_eq!(pwasm_ethereum::gas_limit(), 10000.into());
+	assert_eq!(pwasm_ethereum::block_number(), 7);
+	assert_eq!(pwasm_ethereum::balance(&owner()), U256::from(1000) * U256::from(1_000_000_000_000_000_000u64));
+	assert_eq!(ext_get().nonce(&owner()), 3.into());
+	assert_eq!(H256::from_slice(&pwasm_ethereum::read(&slot(1))), slot(7));
+	assert_eq!(H256::from_slice(&pwasm_ethereum::read(&slot(2))), slot(0xff));
+}
+
+#[pwasm_test]
+fn fresh_world() {
+	assert_eq!(pwasm_ethereum::block_number(), 0);
+}
+
+#[pwasm_test(print_trace, no_unexpected_calls)]
+fn no_calls() {
+	assert!(ext_get().calls().is_empty());
+}
+
+#[pwasm_test(no_unexpected_calls)]
+#[should_panic(expected = "Unexpected calls to addresses without an endpoint")]
+fn unexpected_call() {
+	let mut result = [0u8; 1];
+	let _ = pwasm_ethereum::call(2000, &owner(), 0.into(), &[], &mut result);
+}
+
+#[pwasm_test(no_unexpected_calls)]
+#[should_panic(expected = "STATICCALL")]
+fn unexpected_static_call() {
+	let mut result = [0u8; 1];
+	let _ = pwasm_ethereum::static_call(2000, &owner(), &[], &mut result);
+}
+
+#[pwasm_test(balance("0x16a0772b17ae004e6645e0e95bf50ad69498a34e" = 1000), no_unexpected_calls)]
+fn transactions_are_not_unexpected_calls() {
+	let payee = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	assert!(ext_transact(owner(), payee, 10.into(), &[]).status);
+}
+
+#[pwasm_test(address = "0x35da6abcb08f2b6164fe380bb6c47bd8f2304d55", storage("0x00" = 7), dump_storage, print_trace)]
+#[should_panic(expected = "storage is printed")]
+fn storage_dump_on_panic() {
+	let address = pwasm_ethereum::address();
+	ext_update(|e| e.layout(address, Layout::new().value("totalSupply", 0, "uint256")));
+	panic!("storage is printed");
+}
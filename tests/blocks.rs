@@ -0,0 +1,51 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_mine, ext_advance_time, ext_transact, ext_get, Endpoint};
+use pwasm_test::accounts;
+
+#[test]
+fn mine_blocks() {
+	ext_reset(|e| e.blocknumber(100).timestamp(5000).block_time(10));
+	assert!(pwasm_ethereum::block_hash(100).is_err());
+	ext_mine(3);
+	assert_eq!(pwasm_ethereum::block_number(), 103);
+	assert_eq!(pwasm_ethereum::timestamp(), 5030);
+	let hash = pwasm_ethereum::block_hash(100).unwrap();
+	assert!(hash != pwasm_ethereum::block_hash(101).unwrap());
+	assert!(pwasm_ethereum::block_hash(103).is_err());
+	ext_advance_time(7);
+	assert_eq!(pwasm_ethereum::block_number(), 103);
+	assert_eq!(pwasm_ethereum::timestamp(), 5037);
+	assert_eq!(pwasm_ethereum::block_hash(100).unwrap(), hash);
+}
+
+#[test]
+#[should_panic(expected = "overflows when moved forward by 10 seconds")]
+fn mining_past_the_last_timestamp_fails_with_a_message() {
+	ext_reset(|e| e.timestamp(u64::max_value() - 5).block_time(10));
+	ext_mine(1);
+}
+
+#[test]
+fn logs_are_tagged_with_block() {
+	let contract = accounts::named("contract");
+	ext_reset(|e| e
+		.blocknumber(1)
+		.endpoint(contract, Endpoint::returning(Box::new(|_val, _input| {
+			pwasm_ethereum::log(&[H256::zero()], &[]);
+			Ok(Vec::new())
+		})))
+	);
+	let first = ext_transact(Address::zero(), contract, 0.into(), &[]);
+	ext_mine(1);
+	let second = ext_transact(Address::zero(), contract, 0.into(), &[]);
+	assert_eq!(first.block, 1);
+	assert_eq!(second.block, 2);
+	assert_eq!(second.logs[0].block(), 2);
+	let logs = ext_get().logs();
+	assert_eq!(logs[0].block(), 1);
+	assert_eq!(logs[1].block(), 2);
+}
@@ -1,9 +1,12 @@
 extern crate pwasm_test;
 extern crate pwasm_std;
 extern crate pwasm_ethereum;
+extern crate pwasm_abi;
 
-use pwasm_std::types::Address;
-use pwasm_test::{ext_reset, ext_update, ext_get, Endpoint};
+use pwasm_abi::eth::EndpointInterface;
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_update, ext_get, ext_transact, Endpoint, Error};
+use pwasm_test::accounts::{self, alice};
 
 /// An example of how to use get_external to access "calls" to some contract
 #[test]
@@ -65,3 +68,51 @@ fn calls_update_ext() {
 	pwasm_ethereum::call(2000, &Address::zero(), 10000.into(), &input, &mut result).expect_err("Should be an Error");
 	assert_eq!(ext_get().calls().len(), 2);
 }
+
+#[test]
+fn failed_call_reverts_its_changes() {
+	let callee = accounts::named("callee");
+	ext_reset(|e| e
+		.endpoint(callee, Endpoint::returning(Box::new(|_val, _input| {
+			pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+			pwasm_ethereum::log(&[H256::zero()], &[]);
+			Err(Error)
+		})))
+	);
+	pwasm_ethereum::write(&H256::zero(), &[2; 32]);
+	assert!(pwasm_ethereum::call(20000, &callee, 0.into(), &[], &mut []).is_err());
+	let instance = ext_get();
+	assert_eq!(instance.storage_at(&callee, &H256::zero()), [0; 32]);
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [2; 32]);
+	assert!(instance.logs().is_empty());
+}
+
+/// Contract echoing its input
+struct Echo;
+
+impl EndpointInterface for Echo {
+	fn dispatch(&mut self, payload: &[u8]) -> Vec<u8> {
+		payload.to_vec()
+	}
+
+	fn dispatch_ctor(&mut self, _payload: &[u8]) {}
+}
+
+#[test]
+fn interface_output_fills_the_buffer() {
+	let echo = accounts::named("echo");
+	ext_reset(|e| e.endpoint(echo, Endpoint::from(Echo)));
+	let mut result = [0u8; 2];
+	pwasm_ethereum::call(2000, &echo, 0.into(), &[1, 2], &mut result).unwrap();
+	assert_eq!(result, [1, 2]);
+	assert_eq!(ext_transact(alice(), echo, 0.into(), &[1, 2, 3]).output, vec![1, 2, 3]);
+}
+
+#[test]
+#[should_panic(expected = "Contract returned 3 bytes, but the caller's output buffer has 2 bytes")]
+fn interface_output_of_another_size_panics() {
+	let echo = accounts::named("echo");
+	ext_reset(|e| e.endpoint(echo, Endpoint::from(Echo)));
+	let mut result = [0u8; 2];
+	let _ = pwasm_ethereum::call(2000, &echo, 0.into(), &[1, 2, 3], &mut result);
+}
@@ -0,0 +1,106 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::H256;
+use pwasm_test::{ext_reset, ext_transact, abi_coverage, record_abi_coverage, selector, AbiCoverage, Endpoint, Error};
+use pwasm_test::accounts::{self, alice};
+
+const TOKEN_ABI: &[&str] = &[
+	"transfer(address,uint256)",
+	"pause()",
+	"unpause()",
+	"event Transfer(address,address,uint256)",
+	"event Paused()",
+];
+
+fn transfer_topic() -> H256 {
+	pwasm_test::Signature::parse("Transfer(address,address,uint256)").unwrap().topic()
+}
+
+/// Token which writes slot 1 and logs `Transfer` on transfers and reverts on everything else
+fn token_endpoint() -> Endpoint {
+	Endpoint::returning(Box::new(|_value, input| {
+		if input.len() < 4 || input[..4] != selector("transfer(address,uint256)") {
+			return Err(Error);
+		}
+		pwasm_ethereum::write(&H256::from(1), &[1; 32]);
+		pwasm_ethereum::log(&[transfer_topic()], &[]);
+		Ok(Vec::new())
+	}))
+}
+
+#[test]
+fn methods_reverts_events_and_slots_are_recorded() {
+	record_abi_coverage();
+	let token = accounts::named("coverage token");
+	ext_reset(|e| e
+		.balance_of(alice(), 1000.into())
+		.label(token, "token")
+		.abi(token, TOKEN_ABI)
+		.endpoint(token, token_endpoint())
+	);
+	let mut transfer = selector("transfer(address,uint256)").to_vec();
+	transfer.extend_from_slice(&[0; 64]);
+	assert!(ext_transact(alice(), token, 0.into(), &transfer).status);
+	assert!(ext_transact(alice(), token, 0.into(), &transfer).status);
+	assert!(!ext_transact(alice(), token, 0.into(), &selector("pause()")).status);
+	assert!(!ext_transact(alice(), token, 0.into(), &[]).status);
+
+	let coverage = abi_coverage();
+	let contract = &coverage.contracts[&token];
+	assert_eq!(contract.name, "token");
+
+	let transfer = &contract.methods["transfer(address,uint256)"];
+	assert_eq!(transfer.calls, 2);
+	assert!(transfer.reverts.is_empty());
+	assert_eq!(transfer.events["Transfer(address,address,uint256)"], 2);
+	assert!(transfer.slots.contains("0x0000000000000000000000000000000000000000000000000000000000000001"));
+
+	assert_eq!(contract.methods["pause()"].reverts["reverted"], 1);
+	assert_eq!(contract.methods["unpause()"].calls, 0);
+	assert_eq!(contract.methods["fallback"].calls, 1);
+	assert_eq!(contract.events["Transfer(address,address,uint256)"], 2);
+	assert_eq!(contract.events["Paused()"], 0);
+}
+
+#[test]
+fn accounts_without_code_are_not_recorded() {
+	record_abi_coverage();
+	let payee = accounts::named("coverage payee");
+	ext_reset(|e| e.balance_of(alice(), 1000.into()));
+	assert!(ext_transact(alice(), payee, 10.into(), &[]).status);
+	assert!(!abi_coverage().contracts.contains_key(&payee));
+}
+
+#[test]
+fn unknown_selectors_are_recorded_as_hex() {
+	record_abi_coverage();
+	let token = accounts::named("coverage unknown");
+	ext_reset(|e| e.endpoint(token, Endpoint::ok()));
+	pwasm_ethereum::call(20000, &token, 0.into(), &[0xde, 0xad, 0xbe, 0xef, 0], &mut []).unwrap();
+	assert_eq!(abi_coverage().contracts[&token].methods["0xdeadbeef"].calls, 1);
+}
+
+#[test]
+fn reports_merge_through_json() {
+	let token = accounts::named("coverage merged");
+	let json = r#"{"contracts": {"0x56071e38a32d31a7ee554c883c47c661a7364037": {
+		"name": "token",
+		"events": {"Paused()": 1},
+		"methods": {"pause()": {"calls": 2, "reverts": {"reverted": 1}, "events": {"Paused()": 1}, "slots": ["0x01"]}}
+	}}}"#;
+	let mut coverage = AbiCoverage::from_json(json).unwrap();
+	let other = AbiCoverage::from_json(&coverage.to_json()).unwrap();
+	assert_eq!(other, coverage);
+
+	coverage.merge(&other);
+	let pause = &coverage.contracts[&token].methods["pause()"];
+	assert_eq!(pause.calls, 4);
+	assert_eq!(pause.reverts["reverted"], 2);
+	assert_eq!(pause.slots.len(), 1);
+	assert_eq!(coverage.contracts[&token].events["Paused()"], 2);
+
+	let html = coverage.to_html();
+	assert!(html.contains("<td>pause()</td><td>4</td>"), html);
+}
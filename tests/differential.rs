@@ -0,0 +1,78 @@
+#![cfg(feature = "wasm")]
+
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::Address;
+use pwasm_test::{ext_reset, Differential, Endpoint, WasmContract};
+use pwasm_test::accounts::{self, alice, bob};
+
+/// `call` export which returns `sender()`, see `tests/wasm.rs`
+const RETURN_SENDER: &[u8] = &[
+	0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0d, 0x03, 0x60, 0x01, 0x7f, 0x00, 0x60,
+	0x02, 0x7f, 0x7f, 0x00, 0x60, 0x00, 0x00, 0x02, 0x26, 0x03, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x6d,
+	0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x73, 0x65, 0x6e,
+	0x64, 0x65, 0x72, 0x00, 0x00, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x72, 0x65, 0x74, 0x00, 0x01, 0x03,
+	0x02, 0x01, 0x02, 0x07, 0x08, 0x01, 0x04, 0x63, 0x61, 0x6c, 0x6c, 0x00, 0x02, 0x0a, 0x0e, 0x01,
+	0x0c, 0x00, 0x41, 0x00, 0x10, 0x00, 0x41, 0x00, 0x41, 0x14, 0x10, 0x01, 0x0b,
+];
+
+fn contract() -> Address {
+	accounts::named("contract")
+}
+
+#[test]
+fn identical_runs() {
+	let native = || Endpoint::returning(Box::new(|_val, _input| Ok(pwasm_ethereum::sender().to_vec())));
+	let receipts = Differential::new(contract(), native, WasmContract::from_bytes(RETURN_SENDER).unwrap())
+		.setup(|e| e.balance_of(alice(), 100.into()))
+		.transact(alice(), 10.into(), &[])
+		.transact(bob(), 0.into(), &[1, 2, 3])
+		.run()
+		.unwrap();
+	assert_eq!(receipts.len(), 2);
+	assert_eq!(receipts[1].output, bob().to_vec());
+}
+
+#[test]
+fn diverged_runs() {
+	let native = || Endpoint::returning(Box::new(|_val, _input| {
+		if pwasm_ethereum::sender() == bob() {
+			Ok(vec![0u8; 20])
+		} else {
+			Ok(pwasm_ethereum::sender().to_vec())
+		}
+	}));
+	let divergence = Differential::new(contract(), native, WasmContract::from_bytes(RETURN_SENDER).unwrap())
+		.transact(alice(), 0.into(), &[])
+		.transact(bob(), 0.into(), &[])
+		.run()
+		.unwrap_err();
+	assert_eq!(divergence.step, 1);
+	assert_eq!(divergence.what, "output");
+}
+
+#[test]
+fn nonces_are_compared() {
+	let native = || Endpoint::returning(Box::new(|_val, _input| {
+		pwasm_ethereum::create(0.into(), &[]).unwrap();
+		Ok(pwasm_ethereum::sender().to_vec())
+	}));
+	let divergence = Differential::new(contract(), native, WasmContract::from_bytes(RETURN_SENDER).unwrap())
+		.transact(alice(), 0.into(), &[])
+		.run()
+		.unwrap_err();
+	assert_eq!(divergence.what, "nonce[0xaa5d32ab7ca31126fa375538de047e7475fa8572]");
+}
+
+#[test]
+fn installed_world_is_restored() {
+	ext_reset(|e| e.blocknumber(77));
+	let native = || Endpoint::returning(Box::new(|_val, _input| Ok(pwasm_ethereum::sender().to_vec())));
+	Differential::new(contract(), native, WasmContract::from_bytes(RETURN_SENDER).unwrap())
+		.transact(alice(), 0.into(), &[])
+		.run()
+		.unwrap();
+	assert_eq!(pwasm_ethereum::block_number(), 77);
+}
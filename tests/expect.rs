@@ -0,0 +1,62 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{U256, Address};
+use pwasm_test::{ext_reset, ext_expect_call, selector, AbiArgs, Endpoint};
+use pwasm_test::accounts;
+
+fn owner() -> Address {
+	accounts::alice()
+}
+
+fn token() -> Address {
+	accounts::named("token")
+}
+
+fn transfer(to: Address, amount: u64) {
+	let mut input = selector("transfer(address,uint256)").to_vec();
+	input.extend((to, U256::from(amount)).encode());
+	pwasm_ethereum::call(20000, &token(), 0.into(), &input, &mut []).unwrap();
+}
+
+#[test]
+fn selector_of_transfer() {
+	assert_eq!(selector("transfer(address, uint256)"), [0xa9, 0x05, 0x9c, 0xbb]);
+}
+
+#[test]
+fn matching_calls_are_counted() {
+	ext_reset(|e| e.endpoint(token(), Endpoint::ok()));
+	transfer(owner(), 1);
+	let _transfer = ext_expect_call(token())
+		.with_selector("transfer(address,uint256)")
+		.with_args((owner(), U256::from(100)))
+		.with_value(0.into())
+		.times(2);
+	let _any = ext_expect_call(token());
+	transfer(owner(), 100);
+	transfer(owner(), 50);
+	transfer(owner(), 100);
+}
+
+#[test]
+fn mismatch_shows_decoded_arguments() {
+	ext_reset(|e| e.endpoint(token(), Endpoint::ok()));
+	let expectation = ext_expect_call(token())
+		.with_selector("transfer(address,uint256)")
+		.with_args((owner(), U256::from(100)))
+		.times(1);
+	transfer(owner(), 50);
+	let message = expectation.check().unwrap_err();
+	std::mem::forget(expectation);
+	assert!(message.starts_with("Expected 1 call(s) of 0xba975da4d2459aa3f11d31291a25a8358f84946d.transfer(alice, 100)"));
+	assert!(message.contains("#0 transfer(alice, 50) value 0: args differ from byte 63"));
+}
+
+#[test]
+#[should_panic(expected = "No calls to 0xba975da4d2459aa3f11d31291a25a8358f84946d were made")]
+fn missing_call_panics_at_scope_end() {
+	ext_reset(|e| e.endpoint(token(), Endpoint::ok()));
+	let _expectation = ext_expect_call(token()).with_selector("transfer(address,uint256)");
+}
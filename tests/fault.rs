@@ -0,0 +1,105 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_transact, ext_get, Endpoint, Fault};
+use pwasm_test::accounts;
+
+fn owner() -> Address {
+	accounts::alice()
+}
+
+fn contract() -> Address {
+	accounts::named("contract")
+}
+
+fn token() -> Address {
+	accounts::named("token")
+}
+
+#[test]
+fn nth_call_fails() {
+	ext_reset(|e| e.endpoint(token(), Endpoint::ok()).fail_call(token(), 2));
+	assert!(pwasm_ethereum::call(2000, &token(), 0.into(), &[], &mut []).is_ok());
+	assert!(pwasm_ethereum::call(2000, &token(), 0.into(), &[], &mut []).is_err());
+	assert!(pwasm_ethereum::call(2000, &token(), 0.into(), &[], &mut []).is_ok());
+
+	let instance = ext_get();
+	assert_eq!(instance.calls().len(), 3);
+	assert_eq!(instance.traces()[1].error, Some("injected fault".to_owned()));
+	let report = instance.faults.report();
+	assert_eq!(report.hit, vec![(Fault::CallFails { address: token(), nth: 2 }, 1)]);
+	assert!(report.missed.is_empty());
+}
+
+#[test]
+fn report_lists_missed_faults() {
+	ext_reset(|e| e.fail_create().fail_blockhash());
+	assert!(pwasm_ethereum::block_hash(1).is_err());
+	let report = ext_get().faults.report();
+	assert_eq!(report.hit, vec![(Fault::BlockhashFails, 1)]);
+	assert_eq!(report.missed, vec![Fault::CreateFails]);
+	assert_eq!(report.to_string(), "  hit 1 time(s): blockhash fails\n  not hit: create fails\n");
+}
+
+#[test]
+#[should_panic(expected = "Some injected faults were never hit")]
+fn assert_all_hit_panics_on_missed_faults() {
+	ext_reset(|e| e.fail_create());
+	ext_get().faults.report().assert_all_hit();
+}
+
+#[test]
+fn corrupted_read_only_affects_its_key() {
+	let other = H256::from_slice(&[1; 32]);
+	ext_reset(|e| e
+		.storage(H256::zero(), [1; 32])
+		.storage(other, [2; 32])
+		.corrupt_storage_read(H256::zero(), [0xff; 32])
+	);
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [0xff; 32]);
+	assert_eq!(pwasm_ethereum::read(&other), [2; 32]);
+	assert_eq!(ext_get().storage_at(&Address::zero(), &H256::zero()), [1; 32]);
+}
+
+#[test]
+fn out_of_gas_reverts_transaction() {
+	ext_reset(|e| e
+		.balance_of(owner(), 1000.into())
+		.endpoint(contract(), Endpoint::returning(Box::new(|_val, _input| {
+			pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+			pwasm_ethereum::write(&H256::zero(), &[2; 32]);
+			Ok(Vec::new())
+		})))
+		.out_of_gas_at(2)
+	);
+	let receipt = ext_transact(owner(), contract(), 100.into(), &[]);
+	assert!(!receipt.status);
+	assert_eq!(receipt.trace.error, Some("out of gas".to_owned()));
+	assert_eq!(ext_get().storage_at(&contract(), &H256::zero()), [0; 32]);
+	assert_eq!(pwasm_ethereum::balance(&owner()), 1000.into());
+}
+
+#[test]
+fn out_of_gas_fails_only_the_nested_call() {
+	ext_reset(|e| e
+		.address(contract())
+		.endpoint(token(), Endpoint::new(Box::new(|_val, _input, _result| {
+			pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+			Ok(())
+		})))
+		.out_of_gas_at(2)
+	);
+	assert!(pwasm_ethereum::call(2000, &token(), 0.into(), &[], &mut []).is_err());
+	assert_eq!(pwasm_ethereum::address(), contract());
+	assert_eq!(ext_get().traces()[0].error, Some("out of gas".to_owned()));
+}
+
+#[test]
+#[should_panic(expected = "out of gas injected at extern #2")]
+fn out_of_gas_outside_of_frames_fails_with_a_message() {
+	ext_reset(|e| e.out_of_gas_at(2));
+	pwasm_ethereum::read(&H256::zero());
+	pwasm_ethereum::read(&H256::zero());
+}
@@ -0,0 +1,65 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::Address;
+use pwasm_test::{FuzzTarget, Endpoint};
+use pwasm_test::accounts::{self, alice, bob};
+
+const TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const APPROVE: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+
+fn contract() -> Address {
+	accounts::named("contract")
+}
+
+/// Decoder which panics on an `approve` with a zero argument
+fn target() -> FuzzTarget {
+	FuzzTarget::new(contract(), || Endpoint::returning(Box::new(|_val, input| {
+		if input[..4] == APPROVE && input.get(4) == Some(&0) {
+			panic!("Malformed approve");
+		}
+		Ok(input.to_vec())
+	})))
+		.setup(|e| e.balance_of(alice(), 1000.into()))
+		.sender(alice())
+		.sender(bob())
+		.selector(TRANSFER)
+		.selector(APPROVE)
+}
+
+#[test]
+fn decode_transactions() {
+	let transactions = target().transactions(&[0, 1, 10, 0, 0, 2, 7, 8, 1, 0, 1]);
+	assert_eq!(transactions.len(), 2);
+	assert_eq!(transactions[0].from, alice());
+	assert_eq!(transactions[0].value, 10.into());
+	assert_eq!(transactions[0].input, vec![0xa9, 0x05, 0x9c, 0xbb, 7, 8]);
+	assert_eq!(transactions[1].from, bob());
+	assert_eq!(transactions[1].value, 0.into());
+	assert_eq!(transactions[1].input, APPROVE.to_vec());
+}
+
+#[test]
+fn run_resets_world() {
+	let target = target();
+	let receipts = target.run(&[0, 1, 100, 0, 0, 0]).unwrap();
+	assert!(receipts[0].status);
+	assert_eq!(pwasm_ethereum::balance(&contract()), 100.into());
+	target.run(&[0, 1, 100, 0, 0, 0]).unwrap();
+	assert_eq!(pwasm_ethereum::balance(&contract()), 100.into());
+}
+
+#[test]
+fn panic_is_crash() {
+	let crash = target().run(&[0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 1, 0]).unwrap_err();
+	assert_eq!(crash.step, 1);
+	assert_eq!(crash.message, "Malformed approve");
+	assert_eq!(crash.transactions.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "Contract panicked at transaction #0: Malformed approve")]
+fn fuzz_reports_crash() {
+	target().fuzz(&[1, 0, 1, 0, 1, 0]);
+}
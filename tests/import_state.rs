@@ -0,0 +1,57 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_get, ExternalBuilder};
+
+fn write_fixture(name: &str, json: &str) -> PathBuf {
+	let path = env::temp_dir().join(format!("{}-{}", process::id(), name));
+	File::create(&path).unwrap().write_all(json.as_bytes()).unwrap();
+	path
+}
+
+#[test]
+fn import_from_chain_spec() {
+	let path = write_fixture("pwasm-test-chain-spec.json", r#"{
+		"name": "Dev",
+		"accounts": {
+			"0000000000000000000000000000000000000001": { "balance": "1", "builtin": { "name": "ecrecover" } },
+			"0x35da6abcb08f2b6164fe380bb6c47bd8f2304d55": {
+				"balance": "1000000",
+				"nonce": "0x3",
+				"storage": { "0x02": "0x2710" }
+			}
+		}
+	}"#);
+	let contract: Address = "35da6abcb08f2b6164fe380bb6c47bd8f2304d55".parse().unwrap();
+	ext_reset(|e| e.import_account_state(&path, contract).unwrap().address(contract));
+	let mut key = [0u8; 32];
+	key[31] = 2;
+	let mut value = [0u8; 32];
+	value[30] = 0x27;
+	value[31] = 0x10;
+	assert_eq!(pwasm_ethereum::read(&H256::from_slice(&key)), value);
+	assert_eq!(pwasm_ethereum::balance(&contract), 1000000.into());
+	assert_eq!(ext_get().nonce(&contract), 3.into());
+}
+
+#[test]
+fn import_from_state_dump() {
+	let path = write_fixture("pwasm-test-state-dump.json", r#"{
+		"state": {
+			"0x16a0772b17ae004e6645e0e95bf50ad69498a34e": { "balance": "0x10", "nonce": "0x1", "code": "", "storage": {} }
+		}
+	}"#);
+	let owner: Address = "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap();
+	ext_reset(|e| e.import_account_state(&path, owner).unwrap());
+	assert_eq!(pwasm_ethereum::balance(&owner), 16.into());
+	assert_eq!(ext_get().nonce(&owner), 1.into());
+	assert!(ExternalBuilder::new().import_account_state(&path, Address::zero()).is_err());
+}
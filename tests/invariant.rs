@@ -0,0 +1,62 @@
+#![cfg(all(feature = "proptest", feature = "std"))]
+
+extern crate proptest;
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use proptest::prelude::Just;
+use pwasm_std::types::{H256, U256, Address};
+use pwasm_test::{Invariants, Endpoint};
+use pwasm_test::strategies::u256;
+use pwasm_test::accounts::{self, alice, bob};
+
+const INCREMENT: [u8; 4] = [0xd0, 0x9d, 0xe0, 0x8a];
+const RESET: [u8; 4] = [0xd8, 0x26, 0xf8, 0x8f];
+
+fn contract() -> Address {
+	accounts::named("contract")
+}
+
+/// Counter in slot zero: `increment()` adds one, anything else resets it
+fn counter() -> Endpoint {
+	Endpoint::returning(Box::new(|_val, input| {
+		let counter = U256::from_big_endian(&pwasm_ethereum::read(&H256::zero()));
+		let next = if input[..4] == INCREMENT { counter + U256::one() } else { U256::zero() };
+		let mut value = [0u8; 32];
+		next.to_big_endian(&mut value);
+		pwasm_ethereum::write(&H256::zero(), &value);
+		Ok(Vec::new())
+	}))
+}
+
+fn counter_value(world: &pwasm_test::ExternalInstance) -> U256 {
+	U256::from_big_endian(&world.storage_at(&contract(), &H256::zero()))
+}
+
+#[test]
+fn invariant_holds() {
+	Invariants::new(contract(), counter)
+		.sender(alice())
+		.sender(bob())
+		.action("increment", INCREMENT, Just(()))
+		.action("reset", RESET, (u256(),))
+		.invariant("counter never exceeds depth", |world| counter_value(world) <= 16.into())
+		.runs(32)
+		.check();
+}
+
+#[test]
+fn broken_invariant_is_shrunk() {
+	let failure = Invariants::new(contract(), counter)
+		.sender(alice())
+		.action("increment", INCREMENT, Just(()))
+		.action("reset", RESET, (u256(),))
+		.invariant("counter stays below 3", |world| counter_value(world) < 3.into())
+		.run()
+		.unwrap_err();
+	assert_eq!(failure.invariant, "counter stays below 3");
+	assert_eq!(failure.calls.len(), 3);
+	assert!(failure.calls.iter().all(|call| call.action == "increment"));
+	assert!(failure.to_string().contains("minimal reproduction"));
+}
@@ -0,0 +1,44 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::H256;
+use pwasm_test::{ext_reset, ext_get, set_external, ExternalBuilder};
+use pwasm_test::accounts::{self, alice};
+
+#[test]
+fn dump_and_load_state() {
+	let contract = accounts::named("contract");
+	let owner = alice();
+	ext_reset(|e| e
+		.address(contract)
+		.storage(H256::zero(), [250; 32])
+		.storage_at(owner, H256::from_slice(&[1; 32]), [2; 32])
+		.balance_of(owner, 200000.into())
+		.blocknumber(123123)
+		.timestamp(123124)
+		.difficulty(123.into())
+		.gas_limit(1234.into())
+		.coinbase("c257274276a4e539741ca11b590b9447b26a8051".parse().unwrap())
+	);
+	let json = ext_get().to_json();
+
+	set_external(Box::new(ExternalBuilder::from_json(&json).unwrap().address(contract).build()));
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [250; 32]);
+	assert_eq!(ext_get().storage_at(&owner, &H256::from_slice(&[1; 32])), [2; 32]);
+	assert_eq!(pwasm_ethereum::balance(&owner), 200000.into());
+	assert_eq!(pwasm_ethereum::balance(&contract), 0.into());
+	assert_eq!(pwasm_ethereum::block_number(), 123123);
+	assert_eq!(pwasm_ethereum::timestamp(), 123124);
+	assert_eq!(pwasm_ethereum::difficulty(), 123.into());
+	assert_eq!(pwasm_ethereum::gas_limit(), 1234.into());
+	assert_eq!(pwasm_ethereum::coinbase(), "c257274276a4e539741ca11b590b9447b26a8051".parse().unwrap());
+	assert_eq!(ext_get().to_json(), json);
+}
+
+#[test]
+fn reject_invalid_fixture() {
+	assert!(ExternalBuilder::from_json("not a json").is_err());
+	assert!(ExternalBuilder::from_json(r#"{ "alloc": { "0x1234": {} } }"#).is_err());
+	assert!(ExternalBuilder::from_json(r#"{ "number": "0xzz" }"#).is_err());
+}
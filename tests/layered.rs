@@ -0,0 +1,91 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use pwasm_std::types::{H256, U256, Address};
+use pwasm_test::{set_external, get_external, External, ExternalBuilder, ExternalInstance, Endpoint, Layer, Layered, ReadOnly};
+use pwasm_test::accounts;
+
+fn contract() -> Address {
+	accounts::named("contract")
+}
+
+/// Records names of intercepted methods
+struct Log(&'static str, Rc<RefCell<Vec<String>>>);
+
+impl Layer for Log {
+	fn storage_write(&self, next: &External, key: &H256, value: &[u8; 32]) {
+		self.1.borrow_mut().push(format!("{} write", self.0));
+		next.storage_write(key, value);
+	}
+
+	fn value(&self, next: &External) -> U256 {
+		self.1.borrow_mut().push(format!("{} value", self.0));
+		next.value()
+	}
+}
+
+/// Doubles every stored word
+struct Doubling;
+
+impl Layer for Doubling {
+	fn storage_write(&self, next: &External, key: &H256, value: &[u8; 32]) {
+		let mut doubled = [0u8; 32];
+		for (target, byte) in doubled.iter_mut().zip(value.iter()) {
+			*target = byte.wrapping_mul(2);
+		}
+		next.storage_write(key, &doubled);
+	}
+}
+
+#[test]
+fn layers_are_applied_in_order() {
+	let log = Rc::new(RefCell::new(Vec::new()));
+	set_external(Box::new(Layered::new(ExternalBuilder::new().value(5.into()).build())
+		.layer(Log("outer", log.clone()))
+		.layer(Doubling)
+		.layer(Log("inner", log.clone()))
+	));
+	pwasm_ethereum::write(&H256::zero(), &[3; 32]);
+	assert_eq!(pwasm_ethereum::value(), 5.into());
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [6; 32]);
+	assert_eq!(*log.borrow(), vec!["outer write", "inner write", "outer value", "inner value"]);
+
+	let layered = get_external::<Layered<ExternalInstance>>();
+	assert_eq!(layered.inner().storage_at(&Address::zero(), &H256::zero()), [6; 32]);
+}
+
+#[test]
+fn nested_calls_pass_through_layers() {
+	let log = Rc::new(RefCell::new(Vec::new()));
+	let world = ExternalBuilder::new()
+		.endpoint(contract(), Endpoint::new(Box::new(|_val, _input, _result| {
+			pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+			Ok(())
+		})))
+		.build();
+	set_external(Box::new(Layered::new(world).layer(Log("layer", log.clone()))));
+	pwasm_ethereum::call(20000, &contract(), 0.into(), &[], &mut []).unwrap();
+	assert_eq!(*log.borrow(), vec!["layer write"]);
+	let layered = get_external::<Layered<ExternalInstance>>();
+	assert_eq!(layered.inner().storage_at(&contract(), &H256::zero()), [1; 32]);
+}
+
+#[test]
+#[should_panic(expected = "while storage is read-only")]
+fn read_only_forbids_writes() {
+	set_external(Box::new(Layered::new(ExternalBuilder::new().build()).layer(ReadOnly)));
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [0; 32]);
+	pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+}
+
+#[test]
+fn instance_is_reached_through_inner() {
+	use pwasm_test::{try_get_external, ExtError};
+	set_external(Box::new(Layered::new(ExternalBuilder::new().timestamp(5).build())));
+	assert_eq!(try_get_external::<ExternalInstance>().err(), Some(ExtError::WrongType));
+	assert_eq!(get_external::<Layered<ExternalInstance>>().inner().timestamp, 5);
+}
@@ -0,0 +1,124 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{U256, Address};
+use pwasm_test::{ext_reset, ext_get, ext_dump_storage, ext_assert_storage_layout};
+use pwasm_test::accounts::{self, alice, bob, carol};
+use pwasm_test::layout::{Kind, Layout};
+use pwasm_test::slots::{self, Word};
+
+fn token() -> Address {
+	accounts::named("token")
+}
+
+fn token_layout() -> Layout {
+	Layout::new()
+		.value("totalSupply", 0, "uint256")
+		.mapping("balances", 1, "address", "uint256")
+		.mapping("allowance", 2, "address", "mapping(address => uint256)")
+		.array("holders", 3, "address")
+		.structure("config", 4, &[("owner", "address"), ("paused", "bool")])
+		.mapping("owners", 6, "uint256", "address")
+		.key(U256::from(42))
+}
+
+fn write<T: Word>(key: pwasm_std::types::H256, value: T) {
+	pwasm_ethereum::write(&key, &value.to_word());
+}
+
+#[test]
+fn dump_decodes_declared_variables() {
+	ext_reset(|e| e.address(token()).layout(token(), token_layout()));
+	write(slots::slot(0), U256::from(10000));
+	write(slots::mapping(&slots::slot(1), &alice()), U256::from(9000));
+	write(slots::mapping(&slots::mapping(&slots::slot(2), &alice()), &bob()), U256::from(50));
+	write(slots::slot(3), 2u64);
+	write(slots::array_element(&slots::slot(3), 0, 1), alice());
+	write(slots::array_element(&slots::slot(3), 1, 1), carol());
+	write(slots::slot(4), bob());
+	write(slots::mapping(&slots::slot(6), &U256::from(42)), carol());
+
+	assert_eq!(ext_dump_storage(&token()), [
+		"totalSupply = 10000",
+		"balances[alice] = 9000",
+		"allowance[alice][bob] = 50",
+		"holders.length = 2",
+		"holders[0] = alice",
+		"holders[1] = carol",
+		"config.owner = bob",
+		"config.paused = false",
+		"owners[42] = carol",
+	].join("\n"));
+	ext_assert_storage_layout(&token());
+}
+
+#[test]
+fn structs_in_mappings_and_arrays_are_decoded() {
+	let order = Kind::structure(&[("maker", Kind::of("address")), ("amount", Kind::of("uint256"))]);
+	let layout = Layout::new()
+		.variable("orders", 0, Kind::mapping("address", order.clone()))
+		.variable("queue", 1, Kind::array(order));
+	ext_reset(|e| e.address(token()).layout(token(), layout));
+	let order = slots::mapping(&slots::slot(0), &alice());
+	write(order, bob());
+	write(slots::offset(&order, 1), U256::from(5));
+	let first = slots::array_element(&slots::slot(1), 0, 2);
+	write(slots::slot(1), 1u64);
+	write(first, carol());
+	write(slots::offset(&first, 1), U256::from(7));
+
+	assert_eq!(ext_dump_storage(&token()), [
+		"orders[alice].maker = bob",
+		"orders[alice].amount = 5",
+		"queue.length = 1",
+		"queue[0].maker = carol",
+		"queue[0].amount = 7",
+	].join("\n"));
+	ext_assert_storage_layout(&token());
+}
+
+#[test]
+fn undeclared_writes_are_reported() {
+	ext_reset(|e| e.address(token()).layout(token(), token_layout()));
+	write(slots::slot(0), U256::from(1));
+	write(slots::slot(7), U256::from(1));
+	write(slots::mapping(&slots::slot(6), &U256::from(7)), alice());
+
+	let instance = ext_get();
+	let undeclared = token_layout().undeclared(&instance, &token());
+	assert_eq!(undeclared.len(), 2);
+	assert!(undeclared.contains(&slots::slot(7)));
+	assert!(undeclared.contains(&slots::mapping(&slots::slot(6), &U256::from(7))));
+}
+
+#[test]
+#[should_panic(expected = "has words outside of its layout")]
+fn assert_storage_layout_panics_on_undeclared_writes() {
+	ext_reset(|e| e.address(token()).layout(token(), token_layout()));
+	write(slots::slot(5), true);
+	write(slots::slot(9), true);
+	ext_assert_storage_layout(&token());
+}
+
+#[test]
+#[should_panic(expected = "reserve0: uint128")]
+fn words_wider_than_their_type_are_flagged_as_packed() {
+	let layout = Layout::new().value("reserve0", 0, "uint128");
+	ext_reset(|e| e.address(token()).layout(token(), layout));
+	write(slots::slot(0), (U256::from(1) << 128) + U256::from(5));
+	ext_assert_storage_layout(&token());
+}
+
+#[test]
+#[should_panic(expected = "No storage layout is declared")]
+fn dump_requires_a_layout() {
+	ext_reset(|e| e.address(token()));
+	ext_dump_storage(&token());
+}
+
+#[test]
+#[should_panic(expected = "string is not a storage word type")]
+fn dynamic_types_are_rejected() {
+	Layout::new().value("name", 0, "string");
+}
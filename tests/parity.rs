@@ -0,0 +1,102 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+extern crate serde_json;
+
+use pwasm_std::types::Address;
+use pwasm_test::{ext_reset, ext_transact, ext_get, ext_mine, Endpoint};
+use pwasm_test::accounts;
+use serde_json::Value;
+
+fn owner() -> Address {
+	accounts::alice()
+}
+
+fn token() -> Address {
+	accounts::named("token")
+}
+
+fn oracle() -> Address {
+	accounts::named("oracle")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().fold("0x".to_owned(), |hex, byte| hex + &format!("{:02x}", byte))
+}
+
+fn world() {
+	ext_reset(|e| e
+		.gas_limit(100_000.into())
+		.endpoint(token(), Endpoint::returning(Box::new(|_val, input| {
+			let mut result = [0u8; 1];
+			pwasm_ethereum::call(2000, &oracle(), 5.into(), input, &mut result).unwrap();
+			let _ = pwasm_ethereum::call_code(2000, &owner(), input, &mut result);
+			Ok(result.to_vec())
+		})))
+		.endpoint(oracle(), Endpoint::returning(Box::new(|_val, _input| Ok(vec![7]))))
+	);
+}
+
+#[test]
+fn transaction_traces() {
+	world();
+	let receipt = ext_transact(owner(), token(), 0.into(), &[1]);
+	let traces: Value = serde_json::from_str(&receipt.trace.to_parity_json()).unwrap();
+	let traces = traces.as_array().unwrap();
+	assert_eq!(traces.len(), 3);
+
+	assert_eq!(traces[0]["type"], "call");
+	assert_eq!(traces[0]["action"]["callType"], "call");
+	assert_eq!(traces[0]["action"]["from"], to_hex(&owner()));
+	assert_eq!(traces[0]["action"]["to"], to_hex(&token()));
+	assert_eq!(traces[0]["action"]["gas"], "0x186a0");
+	assert_eq!(traces[0]["action"]["input"], "0x01");
+	assert_eq!(traces[0]["result"]["output"], "0x07");
+	assert_eq!(traces[0]["subtraces"], 2);
+	assert_eq!(traces[0]["traceAddress"], Value::Array(vec![]));
+	assert!(traces[0]["result"].get("gasUsed").is_none());
+	assert_eq!(traces[0]["blockNumber"], 0);
+	assert_eq!(traces[0]["blockHash"], Value::Null);
+	let hash = to_hex(&receipt.trace.transaction_hash());
+	assert_eq!(traces[0]["transactionHash"], Value::String(hash.clone()));
+	assert_eq!(traces[2]["transactionHash"], Value::String(hash));
+
+	assert_eq!(traces[1]["action"]["value"], "0x5");
+	assert_eq!(traces[1]["result"]["output"], "0x07");
+	assert_eq!(traces[1]["traceAddress"], Value::Array(vec![0.into()]));
+
+	assert_eq!(traces[2]["action"]["callType"], "delegatecall");
+	assert_eq!(traces[2]["result"], Value::Null);
+	assert!(traces[2].get("result").is_none());
+	assert_eq!(traces[2]["error"], format!("no endpoint at {}", to_hex(&owner())));
+	assert_eq!(traces[2]["traceAddress"], Value::Array(vec![1.into()]));
+}
+
+#[test]
+fn block_traces() {
+	world();
+	ext_transact(owner(), token(), 0.into(), &[1]);
+	ext_transact(owner(), oracle(), 0.into(), &[]);
+	let traces: Value = serde_json::from_str(&ext_get().traces_to_parity_json()).unwrap();
+	let traces = traces.as_array().unwrap();
+	assert_eq!(traces.len(), 4);
+	assert_eq!(traces[2]["transactionPosition"], 0);
+	assert_eq!(traces[3]["transactionPosition"], 1);
+	assert_eq!(traces[3]["action"]["to"], to_hex(&oracle()));
+	assert!(traces[0]["transactionHash"] != traces[3]["transactionHash"]);
+}
+
+#[test]
+fn block_hashes_of_mined_blocks() {
+	world();
+	ext_transact(owner(), oracle(), 0.into(), &[]);
+	ext_mine(1);
+	ext_transact(owner(), oracle(), 0.into(), &[]);
+	let traces: Value = serde_json::from_str(&ext_get().traces_to_parity_json()).unwrap();
+	let traces = traces.as_array().unwrap();
+	let hash = to_hex(&pwasm_ethereum::block_hash(0).unwrap());
+	assert_eq!(traces[0]["blockNumber"], 0);
+	assert_eq!(traces[0]["blockHash"], Value::String(hash));
+	assert_eq!(traces[1]["blockNumber"], 1);
+	assert_eq!(traces[1]["blockHash"], Value::Null);
+}
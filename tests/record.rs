@@ -0,0 +1,83 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use std::env;
+use std::panic;
+use std::path::PathBuf;
+use std::process;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{set_external, ExternalBuilder, Endpoint, Recorder, Replay};
+use pwasm_test::accounts;
+
+fn token() -> Address {
+	accounts::named("token")
+}
+
+/// Contract logic under test, `increment` is the refactored version's knob
+fn run_contract(increment: u8) {
+	let mut counter = pwasm_ethereum::read(&H256::zero());
+	counter[31] += increment;
+	pwasm_ethereum::write(&H256::zero(), &counter);
+	let mut result = [0u8; 1];
+	pwasm_ethereum::call(5000, &token(), 0.into(), &counter, &mut result).unwrap();
+	pwasm_ethereum::log(&[H256::zero()], &result);
+}
+
+fn record(name: &str) -> PathBuf {
+	let recorder = Recorder::new(ExternalBuilder::new()
+		.endpoint(token(), Endpoint::new(Box::new(|_val, _input, result| {
+			result[0] = pwasm_ethereum::timestamp() as u8;
+			Ok(())
+		})))
+		.timestamp(42)
+		.build()
+	);
+	set_external(Box::new(recorder.clone()));
+	run_contract(1);
+	let interactions = recorder.interactions();
+	assert_eq!(interactions.len(), 5);
+	assert_eq!(interactions[2].name, "call");
+	assert_eq!(interactions[3].name, "timestamp");
+	assert_eq!(interactions[3].depth, 1);
+
+	let path = env::temp_dir().join(format!("{}-{}", process::id(), name));
+	recorder.save(&path).unwrap();
+	path
+}
+
+#[test]
+fn replay_recording() {
+	let replay = Replay::load(record("pwasm-test-replay.jsonl")).unwrap();
+	assert_eq!(replay.remaining(), 4);
+	set_external(Box::new(replay.clone()));
+	run_contract(1);
+	assert!(replay.is_finished());
+}
+
+#[test]
+#[should_panic(expected = "Replay diverged at interaction #1")]
+fn replay_divergence() {
+	let replay = Replay::load(record("pwasm-test-diverged.jsonl")).unwrap();
+	set_external(Box::new(replay));
+	run_contract(2);
+}
+
+#[test]
+fn depth_is_restored_after_a_panicking_call() {
+	let recorder = Recorder::new(ExternalBuilder::new()
+		.endpoint(token(), Endpoint::new(Box::new(|_val, input, _result| {
+			assert!(!input.is_empty(), "endpoint failed");
+			Ok(())
+		})))
+		.build()
+	);
+	set_external(Box::new(recorder.clone()));
+	let result = panic::catch_unwind(|| pwasm_ethereum::call(5000, &token(), 0.into(), &[], &mut []));
+	assert!(result.is_err());
+	pwasm_ethereum::timestamp();
+	let interactions = recorder.interactions();
+	assert_eq!(interactions[1].name, "timestamp");
+	assert_eq!(interactions[1].depth, 0);
+}
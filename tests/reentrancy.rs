@@ -0,0 +1,120 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use std::any::Any;
+use std::cell::Cell;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_update, ext_transact, ext_get, set_external, get_external, Endpoint, External};
+use pwasm_test::accounts;
+
+fn owner() -> Address {
+	accounts::alice()
+}
+
+fn vault() -> Address {
+	accounts::named("vault")
+}
+
+fn bank() -> Address {
+	accounts::named("bank")
+}
+
+#[test]
+fn update_world_inside_endpoint() {
+	ext_reset(|e| e
+		.endpoint(vault(), Endpoint::returning(Box::new(|_val, _input| {
+			ext_update(|e| e.timestamp(1000));
+			pwasm_ethereum::write(&H256::zero(), &[pwasm_ethereum::timestamp() as u8; 32]);
+			Ok(pwasm_ethereum::sender().to_vec())
+		})))
+	);
+	let receipt = ext_transact(owner(), vault(), 0.into(), &[]);
+	assert!(receipt.status);
+	assert_eq!(receipt.output, owner().to_vec());
+	assert_eq!(pwasm_ethereum::timestamp(), 1000);
+	assert_eq!(ext_get().storage_at(&vault(), &H256::zero()), [1000u64 as u8; 32]);
+	assert!(receipt.trace.success);
+}
+
+#[test]
+fn replace_world_inside_nested_call() {
+	ext_reset(|e| e
+		.endpoint(vault(), Endpoint::returning(Box::new(|_val, input| {
+			let mut result = [0u8; 1];
+			pwasm_ethereum::call(2000, &bank(), 0.into(), input, &mut result).unwrap();
+			pwasm_ethereum::write(&H256::zero(), &[result[0]; 32]);
+			Ok(result.to_vec())
+		})))
+		.endpoint(bank(), Endpoint::returning(Box::new(|_val, _input| {
+			let mut world = get_external::<pwasm_test::ExternalInstance>();
+			world.blocknumber = 42;
+			set_external(Box::new(world));
+			Ok(vec![pwasm_ethereum::block_number() as u8])
+		})))
+	);
+	let receipt = ext_transact(owner(), vault(), 0.into(), &[]);
+	assert!(receipt.status);
+	assert_eq!(receipt.output, vec![42]);
+	assert_eq!(ext_get().storage_at(&vault(), &H256::zero()), [42; 32]);
+	assert_eq!(receipt.trace.calls.len(), 1);
+	assert!(receipt.trace.calls[0].success);
+}
+
+#[test]
+fn reentrant_endpoint() {
+	ext_reset(|e| e
+		.endpoint(vault(), Endpoint::returning(Box::new(|_val, input| {
+			if input.is_empty() {
+				// Withdraws through the bank, which calls back into the vault
+				let mut result = [0u8; 1];
+				pwasm_ethereum::call(2000, &bank(), 0.into(), &[], &mut result).unwrap();
+				Ok(result.to_vec())
+			} else {
+				pwasm_ethereum::write(&H256::zero(), &[7; 32]);
+				Ok(vec![pwasm_ethereum::sender()[0]])
+			}
+		})))
+		.endpoint(bank(), Endpoint::returning(Box::new(|_val, _input| {
+			let mut result = [0u8; 1];
+			pwasm_ethereum::call(2000, &vault(), 0.into(), &[1], &mut result).unwrap();
+			Ok(result.to_vec())
+		})))
+	);
+	let receipt = ext_transact(owner(), vault(), 0.into(), &[]);
+	assert!(receipt.status);
+	assert_eq!(receipt.output, vec![bank()[0]]);
+	assert_eq!(ext_get().storage_at(&vault(), &H256::zero()), [7; 32]);
+	assert_eq!(receipt.trace.calls[0].calls[0].to, vault());
+	assert!(receipt.trace.calls[0].calls[0].success);
+}
+
+/// External which counts reads with interior mutability and calls back into the externs
+#[derive(Clone, Default)]
+struct CountingExternal {
+	reads: Cell<u32>,
+}
+
+impl External for CountingExternal {
+	fn storage_read(&self, _key: &H256) -> [u8; 32] {
+		self.reads.set(self.reads.get() + 1);
+		[pwasm_ethereum::block_number() as u8; 32]
+	}
+
+	fn blocknumber(&self) -> u64 {
+		u64::from(self.reads.get())
+	}
+
+	fn as_any(&self) -> &Any {
+		self
+	}
+}
+
+#[test]
+fn nested_externs_in_custom_external() {
+	set_external(Box::new(CountingExternal::default()));
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [1; 32]);
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [2; 32]);
+	assert_eq!(get_external::<CountingExternal>().reads.get(), 2);
+}
@@ -0,0 +1,45 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use std::any::Any;
+
+use pwasm_std::types::Address;
+use pwasm_test::{ext_reset, ext_scope, try_get_external, External, ExternalInstance, ExtGuard, ExtError};
+
+#[derive(Clone)]
+struct FixedSender;
+
+impl External for FixedSender {
+	fn sender(&self) -> Address {
+		"16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap()
+	}
+
+	fn as_any(&self) -> &Any {
+		self
+	}
+}
+
+#[test]
+fn nested_scopes() {
+	ext_reset(|e| e.blocknumber(1));
+	{
+		let _outer = ext_scope(|e| e.blocknumber(2));
+		{
+			let _inner = ext_scope(|e| e.blocknumber(3));
+			assert_eq!(pwasm_ethereum::block_number(), 3);
+		}
+		assert_eq!(pwasm_ethereum::block_number(), 2);
+	}
+	assert_eq!(pwasm_ethereum::block_number(), 1);
+}
+
+#[test]
+fn typed_access() {
+	let guard = ExtGuard::install(Box::new(FixedSender));
+	assert_eq!(pwasm_ethereum::sender(), "16a0772b17ae004e6645e0e95bf50ad69498a34e".parse().unwrap());
+	assert!(guard.get::<FixedSender>().is_ok());
+	assert_eq!(guard.get::<ExternalInstance>().err(), Some(ExtError::WrongType));
+	drop(guard);
+	assert!(try_get_external::<ExternalInstance>().is_ok());
+}
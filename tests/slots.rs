@@ -0,0 +1,83 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{H256, U256, Address};
+use pwasm_test::{ext_reset, ext_get, ext_set_storage_u256, ext_set_storage_address, ext_set_storage_bool};
+use pwasm_test::accounts::{alice, bob};
+use pwasm_test::slots::{self, Word};
+
+#[test]
+fn mapping_slot_is_keccak_of_key_and_slot() {
+	let zero_key: H256 = "ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5".parse().unwrap();
+	assert_eq!(slots::mapping(&slots::slot(0), &U256::zero()), zero_key);
+	assert_eq!(slots::mapping(&slots::slot(0), &Address::zero()), zero_key);
+	assert_ne!(slots::mapping(&slots::slot(1), &alice()), slots::mapping(&slots::slot(1), &bob()));
+}
+
+#[test]
+fn array_elements_follow_keccak_of_slot() {
+	let start: H256 = "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563".parse().unwrap();
+	assert_eq!(slots::array_start(&slots::slot(0)), start);
+	assert_eq!(slots::array_element(&slots::slot(0), 0, 1), start);
+	assert_eq!(slots::array_element(&slots::slot(0), 2, 3), slots::offset(&start, 6));
+	assert_eq!(slots::offset(&slots::slot(4), 1), slots::slot(5));
+}
+
+#[test]
+fn offset_wraps_around() {
+	let last = H256::from_slice(&[0xff; 32]);
+	assert_eq!(slots::offset(&last, 1), slots::slot(0));
+}
+
+#[test]
+fn element_offsets_beyond_u64_do_not_overflow() {
+	let start = slots::array_start(&slots::slot(0));
+	let max = u64::max_value();
+	assert_eq!(slots::array_element(&slots::slot(0), max, 2), slots::offset(&slots::offset(&start, max), max));
+}
+
+#[test]
+fn words_are_right_aligned() {
+	let word = alice().to_word();
+	assert_eq!(&word[..12], &[0u8; 12]);
+	assert_eq!(Address::from_word(&word), alice());
+	assert_eq!(U256::from_word(&1000u64.to_word()), 1000.into());
+	assert_eq!(true.to_word()[31], 1);
+	assert!(!bool::from_word(&[0; 32]));
+}
+
+#[test]
+fn typed_storage_round_trips() {
+	let owner = slots::slot(0);
+	let paused = slots::slot(1);
+	let balance = slots::mapping(&slots::slot(2), &alice());
+	ext_reset(|e| e
+		.storage_value(owner, alice())
+		.storage_value(paused, true)
+		.storage_value(balance, U256::from(9000))
+	);
+
+	let instance = ext_get();
+	assert_eq!(instance.storage_address(&owner), alice());
+	assert!(instance.storage_bool(&paused));
+	assert_eq!(instance.storage_u256(&balance), 9000.into());
+	assert_eq!(U256::from_word(&pwasm_ethereum::read(&balance)), 9000.into());
+
+	ext_set_storage_u256(&balance, 1.into());
+	ext_set_storage_bool(&paused, false);
+	ext_set_storage_address(&owner, bob());
+	assert_eq!(U256::from_word(&pwasm_ethereum::read(&balance)), 1.into());
+	assert!(!bool::from_word(&pwasm_ethereum::read(&paused)));
+	assert_eq!(Address::from_word(&pwasm_ethereum::read(&owner)), bob());
+	assert_eq!(ext_get().storage_value::<Address>(&owner), bob());
+}
+
+#[test]
+fn setting_storage_of_a_copy_keeps_installed_world() {
+	ext_reset(|e| e.storage_value(slots::slot(0), true));
+	let instance = ext_get();
+	instance.set_storage_bool(&slots::slot(0), false);
+	assert!(!instance.storage_bool(&slots::slot(0)));
+	assert!(bool::from_word(&pwasm_ethereum::read(&slots::slot(0))));
+}
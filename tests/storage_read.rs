@@ -3,10 +3,23 @@ extern crate pwasm_std;
 extern crate pwasm_ethereum;
 
 use pwasm_std::types::H256;
-use pwasm_test::ext_reset;
+use pwasm_test::{ext_reset, ext_update, ext_get};
+use pwasm_test::accounts;
 
 #[test]
 fn read_storage() {
 	ext_reset(|e| e.storage(H256::zero(), [250; 32]));
 	assert_eq!([250; 32], pwasm_ethereum::read(&H256::zero()));
 }
+
+#[test]
+fn changing_address_keeps_storage_of_accounts() {
+	let first = accounts::named("first");
+	let second = accounts::named("second");
+	ext_reset(|e| e.address(first).storage(H256::zero(), [1; 32]));
+	ext_update(|e| e.address(second).storage(H256::zero(), [2; 32]));
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [2; 32]);
+	assert_eq!(ext_get().storage_at(&first, &H256::zero()), [1; 32]);
+	ext_update(|e| e.address(first));
+	assert_eq!(pwasm_ethereum::read(&H256::zero()), [1; 32]);
+}
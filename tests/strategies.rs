@@ -0,0 +1,30 @@
+#![cfg(all(feature = "proptest", feature = "std"))]
+
+#[macro_use]
+extern crate proptest;
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_test::ext_reset;
+use pwasm_test::strategies::{abi_input, address, builder_config, u256};
+
+proptest! {
+	#[test]
+	fn builder_config_is_valid(config in builder_config()) {
+		ext_reset(|e| config.apply(e));
+		prop_assert_eq!(pwasm_ethereum::sender(), config.sender);
+		prop_assert_eq!(pwasm_ethereum::address(), config.address);
+		prop_assert!(config.balances.contains_key(&config.sender));
+		prop_assert!(pwasm_ethereum::value() <= pwasm_ethereum::balance(&config.sender));
+		for (key, value) in &config.storage {
+			prop_assert_eq!(&pwasm_ethereum::read(key), value);
+		}
+	}
+
+	#[test]
+	fn abi_input_is_encoded(input in abi_input([0xa9, 0x05, 0x9c, 0xbb], (address(), u256()))) {
+		prop_assert_eq!(input.len(), 4 + 2 * 32);
+		prop_assert_eq!(&input[..4], &[0xa9, 0x05, 0x9c, 0xbb]);
+	}
+}
@@ -0,0 +1,98 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_transact, ext_get, Endpoint, CallKind, Error};
+use pwasm_test::accounts::{self, alice};
+
+fn token() -> Address {
+	accounts::named("token")
+}
+
+fn oracle() -> Address {
+	accounts::named("oracle")
+}
+
+/// Token stores the oracle's answer and logs it, oracle reports its caller's address
+fn world() {
+	ext_reset(|e| e
+		.gas_limit(1_000_000.into())
+		.label(token(), "token")
+		.label(oracle(), "oracle")
+		.endpoint(token(), Endpoint::returning(Box::new(|_val, input| {
+			let mut answer = [0u8; 20];
+			pwasm_ethereum::call(2000, &oracle(), 0.into(), input, &mut answer).unwrap();
+			let mut word = [0u8; 32];
+			word[12..].copy_from_slice(&answer);
+			pwasm_ethereum::write(&H256::zero(), &word);
+			pwasm_ethereum::log(&[H256::zero()], &answer);
+			Ok(answer.to_vec())
+		})))
+		.endpoint(oracle(), Endpoint::returning(Box::new(|_val, _input| {
+			pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+			Ok(pwasm_ethereum::sender().to_vec())
+		})))
+	);
+}
+
+#[test]
+fn nested_frames() {
+	world();
+	let receipt = ext_transact(alice(), token(), 0.into(), &[1, 2]);
+	assert!(receipt.status);
+	let trace = receipt.trace;
+	assert_eq!(trace.kind, CallKind::Call);
+	assert_eq!(trace.from, alice());
+	assert_eq!(trace.to, token());
+	assert_eq!(trace.gas, 1_000_000);
+	assert_eq!(trace.input, vec![1, 2]);
+	assert_eq!(trace.output, token().to_vec());
+	assert!(trace.success);
+	assert_eq!(trace.logs.len(), 1);
+	assert_eq!(trace.storage_writes.len(), 1);
+	assert_eq!(trace.storage_writes[0].address, token());
+
+	assert_eq!(trace.calls.len(), 1);
+	let nested = &trace.calls[0];
+	assert_eq!(nested.kind, CallKind::Call);
+	assert_eq!(nested.from, token());
+	assert_eq!(nested.to, oracle());
+	assert_eq!(nested.gas, 2000);
+	assert_eq!(nested.output, token().to_vec());
+	assert_eq!(nested.storage_writes[0].address, oracle());
+	assert_eq!(ext_get().storage_at(&oracle(), &H256::zero()), [1; 32]);
+	assert_eq!(ext_get().traces().len(), 1);
+}
+
+#[test]
+fn failed_frame() {
+	let missing: Address = "0000000000000000000000000000000000000001".parse().unwrap();
+	ext_reset(|e| e
+		.endpoint(token(), Endpoint::returning(Box::new(move |_val, input| {
+			let mut result = [0u8; 1];
+			pwasm_ethereum::call(2000, &missing, 0.into(), input, &mut result).map_err(|_| Error)?;
+			Ok(result.to_vec())
+		})))
+	);
+	let receipt = ext_transact(alice(), token(), 0.into(), &[]);
+	assert!(!receipt.status);
+	assert_eq!(receipt.trace.error, Some("reverted".to_owned()));
+	assert_eq!(receipt.trace.calls[0].error, Some("no endpoint at 0x0000000000000000000000000000000000000001".to_owned()));
+	assert!(!receipt.trace.calls[0].success);
+}
+
+#[test]
+fn display() {
+	world();
+	let receipt = ext_transact(alice(), token(), 0.into(), &[1, 2]);
+	assert_eq!(receipt.trace.to_string(), [
+		"[1000000] CALL alice -> token input 0x0102",
+		"├─ [2000] CALL token -> oracle input 0x0102",
+		"│  ├─ storage[0x0000000000000000000000000000000000000000000000000000000000000000] = 0x0101010101010101010101010101010101010101010101010101010101010101",
+		"│  └─ ← 0xba975da4d2459aa3f11d31291a25a8358f84946d",
+		"├─ storage[0x0000000000000000000000000000000000000000000000000000000000000000] = 0x000000000000000000000000ba975da4d2459aa3f11d31291a25a8358f84946d",
+		"├─ emit topics [0x0000000000000000000000000000000000000000000000000000000000000000] data 0xba975da4d2459aa3f11d31291a25a8358f84946d",
+		"└─ ← 0xba975da4d2459aa3f11d31291a25a8358f84946d",
+	].join("\n"));
+}
@@ -0,0 +1,85 @@
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::{H256, Address};
+use pwasm_test::{ext_reset, ext_transact, ext_get, Endpoint, Error};
+use pwasm_test::accounts;
+
+fn owner() -> Address {
+	accounts::alice()
+}
+
+fn contract() -> Address {
+	accounts::named("contract")
+}
+
+#[test]
+fn transaction_commits_state() {
+	ext_reset(|e| e
+		.balance_of(owner(), 1000.into())
+		.endpoint(contract(), Endpoint::returning(Box::new(|_val, input| {
+			pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+			pwasm_ethereum::log(&[H256::zero()], input);
+			Ok(pwasm_ethereum::origin().to_vec())
+		})))
+	);
+	let receipt = ext_transact(owner(), contract(), 400.into(), &[0, 1]);
+	assert!(receipt.status);
+	assert_eq!(&receipt.output[..], &owner()[..]);
+	assert_eq!(receipt.logs.len(), 1);
+	assert_eq!(receipt.logs[0].data.as_ref(), &[0, 1]);
+	assert_eq!(receipt.gas_used, 21000 + 4 + 68);
+	assert_eq!(ext_get().storage_at(&contract(), &H256::zero()), [1; 32]);
+	assert_eq!(pwasm_ethereum::balance(&owner()), 600.into());
+	assert_eq!(pwasm_ethereum::balance(&contract()), 400.into());
+}
+
+#[test]
+fn failed_transaction_reverts_state() {
+	ext_reset(|e| e
+		.balance_of(owner(), 1000.into())
+		.endpoint(contract(), Endpoint::returning(Box::new(|_val, _input| {
+			pwasm_ethereum::write(&H256::zero(), &[1; 32]);
+			pwasm_ethereum::log(&[H256::zero()], &[]);
+			Err(Error)
+		})))
+	);
+	let receipt = ext_transact(owner(), contract(), 400.into(), &[]);
+	assert!(!receipt.status);
+	assert!(receipt.logs.is_empty());
+	assert_eq!(ext_get().storage_at(&contract(), &H256::zero()), [0; 32]);
+	assert_eq!(pwasm_ethereum::balance(&owner()), 1000.into());
+	assert!(ext_get().logs().is_empty());
+}
+
+#[test]
+fn transaction_records_calls() {
+	ext_reset(|e| e
+		.balance_of(owner(), 0.into())
+		.endpoint(contract(), Endpoint::new(Box::new(|_val, input, result| {
+			pwasm_ethereum::call(1000, &Address::zero(), 0.into(), input, result).expect_err("No endpoint at zero address");
+			Ok(())
+		})))
+	);
+	let receipt = ext_transact(owner(), contract(), 0.into(), &[7]);
+	assert!(receipt.status);
+	assert!(receipt.output.is_empty());
+	assert_eq!(receipt.calls.len(), 1);
+	assert_eq!(receipt.calls[0].address, Address::zero());
+	assert_eq!(receipt.calls[0].input.as_ref(), &[7]);
+}
+
+#[test]
+fn transaction_overflowing_recipient_balance_fails() {
+	use pwasm_std::types::U256;
+	ext_reset(|e| e
+		.balance_of(owner(), 1000.into())
+		.balance_of(contract(), U256::max_value())
+	);
+	let receipt = ext_transact(owner(), contract(), 1.into(), &[]);
+	assert!(!receipt.status);
+	assert_eq!(pwasm_ethereum::balance(&owner()), 1000.into());
+	assert_eq!(pwasm_ethereum::balance(&contract()), U256::max_value());
+	assert_eq!(ext_get().nonce(&owner()), 0.into());
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "wasm")]
+
+extern crate pwasm_test;
+extern crate pwasm_std;
+extern crate pwasm_ethereum;
+
+use pwasm_std::types::Address;
+use pwasm_test::{ext_reset, ext_transact, WasmContract, WasmError};
+use pwasm_test::accounts;
+
+/// `call` export which returns `sender()`:
+///
+/// ```wat
+/// (module
+///   (import "env" "memory" (memory 1))
+///   (import "env" "sender" (func $sender (param i32)))
+///   (import "env" "ret" (func $ret (param i32 i32)))
+///   (func (export "call")
+///     (call $sender (i32.const 0))
+///     (call $ret (i32.const 0) (i32.const 20))))
+/// ```
+const RETURN_SENDER: &[u8] = &[
+	0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0d, 0x03, 0x60, 0x01, 0x7f, 0x00, 0x60,
+	0x02, 0x7f, 0x7f, 0x00, 0x60, 0x00, 0x00, 0x02, 0x26, 0x03, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x6d,
+	0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x73, 0x65, 0x6e,
+	0x64, 0x65, 0x72, 0x00, 0x00, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x72, 0x65, 0x74, 0x00, 0x01, 0x03,
+	0x02, 0x01, 0x02, 0x07, 0x08, 0x01, 0x04, 0x63, 0x61, 0x6c, 0x6c, 0x00, 0x02, 0x0a, 0x0e, 0x01,
+	0x0c, 0x00, 0x41, 0x00, 0x10, 0x00, 0x41, 0x00, 0x41, 0x14, 0x10, 0x01, 0x0b,
+];
+
+/// `(module (import "env" "foo" (func (param i32))))`
+const UNKNOWN_IMPORT: &[u8] = &[
+	0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x01, 0x7f, 0x00, 0x02,
+	0x0b, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x66, 0x6f, 0x6f, 0x00, 0x00,
+];
+
+fn sender() -> Address {
+	accounts::alice()
+}
+
+#[test]
+fn call_binary() {
+	ext_reset(|e| e.sender(sender()));
+	let contract = WasmContract::from_bytes(RETURN_SENDER).unwrap();
+	assert_eq!(contract.call(&[]).unwrap(), sender().to_vec());
+}
+
+#[test]
+fn binary_as_endpoint() {
+	let address = accounts::named("contract");
+	ext_reset(|e| e.endpoint(address, WasmContract::from_bytes(RETURN_SENDER).unwrap().into()));
+	let receipt = ext_transact(sender(), address, 0.into(), &[]);
+	assert!(receipt.status);
+	assert_eq!(receipt.output, sender().to_vec());
+}
+
+#[test]
+fn unknown_import() {
+	let contract = WasmContract::from_bytes(UNKNOWN_IMPORT).unwrap();
+	match contract.call(&[]) {
+		Err(WasmError::Interpreter(_)) => (),
+		other => panic!("Expected instantiation error, got {:?}", other),
+	}
+}